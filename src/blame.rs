@@ -0,0 +1,180 @@
+//! Annotates scan matches with the author and commit that last changed their line, for
+//! `scan --blame`.
+//!
+//! `git blame` runs once per file (in batches of `BLAME_BATCH` lines), asking only for
+//! the matched lines. Files outside a git repository, untracked files and lines that
+//! are not committed yet are left without an author.
+
+use crate::errors::Result;
+use crate::scanner::{resolve_workers, Match};
+use log::{debug, warn};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The most lines passed to a single `git blame` call, to keep command lines short.
+const BLAME_BATCH: usize = 200;
+
+/// The author and commit that last changed a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BlameLine {
+    author: String,
+    commit: String,
+}
+
+/// Sets `author` and `commit` on every match whose line git can blame, running the
+/// per-file `git blame` calls on `workers` threads.
+pub fn attach_blame(matches: &mut [Match], workers: Option<usize>) -> Result<()> {
+    let mut lines_by_file: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+    for m in matches.iter() {
+        lines_by_file.entry(m.file_path.clone()).or_default().push(m.line_number);
+    }
+    if lines_by_file.is_empty() {
+        return Ok(());
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(resolve_workers(workers))
+        .build()?;
+    let blamed: HashMap<PathBuf, HashMap<usize, BlameLine>> = pool.install(|| {
+        lines_by_file
+            .into_par_iter()
+            .filter_map(|(path, mut lines)| {
+                lines.sort_unstable();
+                lines.dedup();
+                let blame = blame_lines(&path, &lines)?;
+                Some((path, blame))
+            })
+            .collect()
+    });
+
+    if blamed.is_empty() {
+        warn!("--blame: no matched file could be blamed (is it inside a git repository?)");
+    }
+    for m in matches.iter_mut() {
+        if let Some(line) = blamed.get(&m.file_path).and_then(|b| b.get(&m.line_number)) {
+            m.author = Some(line.author.clone());
+            m.commit = Some(line.commit.clone());
+        }
+    }
+
+    Ok(())
+}
+
+/// Blames `lines` of `path`, or returns `None` if git cannot (no repository, untracked
+/// file, git missing). Uncommitted lines are left out of the result.
+fn blame_lines(path: &Path, lines: &[usize]) -> Option<HashMap<usize, BlameLine>> {
+    let dir = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file_name = path.file_name()?;
+
+    let mut blamed = HashMap::new();
+    for batch in lines.chunks(BLAME_BATCH) {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["blame", "--line-porcelain"])
+            .args(batch.iter().map(|line| format!("-L{line},{line}")))
+            .arg("--")
+            .arg(file_name)
+            .output();
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                debug!("Cannot blame {}: {}", path.display(), stderr.trim());
+                return None;
+            }
+            Err(e) => {
+                debug!("Cannot run git blame for {}: {e}", path.display());
+                return None;
+            }
+        };
+        blamed.extend(parse_line_porcelain(&String::from_utf8_lossy(&output.stdout)));
+    }
+
+    Some(blamed)
+}
+
+/// Parses `git blame --line-porcelain` output into the blame of each final line number.
+///
+/// Each line's entry starts with `<commit> <original line> <final line> [<count>]`,
+/// followed by `key value` headers and the line itself prefixed with a tab.
+fn parse_line_porcelain(output: &str) -> HashMap<usize, BlameLine> {
+    let mut blamed = HashMap::new();
+    let mut entry: Option<(String, usize)> = None;
+    let mut author = None;
+
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            // Not committed yet lines are blamed on the all-zero commit
+            if let (Some((commit, line_number)), Some(author)) = (entry.take(), author.take())
+                && commit.bytes().any(|b| b != b'0')
+            {
+                blamed.insert(line_number, BlameLine { author, commit });
+            }
+        } else if entry.is_none() {
+            let mut fields = line.split(' ');
+            let commit = fields.next().unwrap_or_default();
+            let line_number = fields.nth(1).and_then(|n| n.parse().ok());
+            if let Some(line_number) = line_number {
+                entry = Some((commit.to_string(), line_number));
+            }
+        } else if let Some(name) = line.strip_prefix("author ") {
+            author = Some(name.to_string());
+        }
+    }
+
+    blamed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Args, Commands};
+    use crate::scanner::run_scan;
+    use clap::Parser;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_blame_attaches_author_of_committed_lines() {
+        let repo = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").arg("-C").arg(repo.path()).args(args).output();
+            assert!(status.unwrap().status.success(), "git {args:?} failed");
+        };
+        git(&["init", "-q"]);
+        fs::write(repo.path().join("app.js"), "// TODO: committed\n").unwrap();
+        git(&["add", "app.js"]);
+        git(&[
+            "-c", "user.name=Ann Author", "-c", "user.email=ann@example.com",
+            "-c", "commit.gpgsign=false", "commit", "-qm", "Add app",
+        ]);
+        fs::write(repo.path().join("app.js"), "// TODO: committed\n// TODO: new\n").unwrap();
+        fs::write(repo.path().join("untracked.js"), "// TODO: untracked\n").unwrap();
+
+        let output = repo.path().join("out.json");
+        let argv = [
+            "ob", "scan", "-e", "TODO", "-x", "js", "-f", "json", "--blame", "--sort-by", "path",
+            "-o", output.to_str().unwrap(), repo.path().to_str().unwrap(),
+        ];
+        let Commands::Scan(args) = Args::try_parse_from(argv).unwrap().command else {
+            unreachable!()
+        };
+        run_scan(*args).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        let matches = json["matches"].as_array().unwrap();
+        assert_eq!(matches.len(), 3);
+        assert_eq!(matches[0]["author"], "Ann Author");
+        assert_eq!(matches[0]["commit"].as_str().unwrap().len(), 40);
+        // The uncommitted line and the untracked file have no author
+        assert!(matches[1].get("author").is_none(), "{}", matches[1]);
+        assert!(matches[2].get("author").is_none(), "{}", matches[2]);
+    }
+}
@@ -1,5 +1,11 @@
+use crate::config::LineEnding;
+use crate::lexer::CodeContext;
+use crate::output_formatter::{CsvMode, SummaryTop};
+use crate::scanner::TextEncoding;
 use clap::{Parser, Subcommand};
+use std::ops::Range;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// A blazing-fast code scanner and transformer for monoliths.
 ///
@@ -34,6 +40,29 @@ pub struct Args {
     pub command: Commands,
 }
 
+/// Orders in which `scan` can list its matches.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    /// By file path, then line number and column.
+    Path,
+    /// Files with the most matches first, then by path, line number and column.
+    Count,
+}
+
+/// Output formats for the `replace` report.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A short summary of files scanned and changed.
+    #[default]
+    Text,
+    /// A JSON report with per-file and per-pattern change counts.
+    Json,
+    /// SARIF results whose `fixes` hold each edit, for inline display in review tools.
+    Sarif,
+    /// GitHub review suggestion blocks, one per changed region. Requires `--dry-run`.
+    GithubSuggestions,
+}
+
 /// Pre-defined sets of patterns for common replacement tasks.
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub enum Preset {
@@ -51,6 +80,8 @@ pub enum Preset {
     TabsToSpaces,
     /// Convert sequences of spaces to hard tabs.
     SpacesToTabs,
+    /// Convert every line ending to `\n` (or to `\r\n` with `--line-endings crlf`).
+    NormalizeLineEndings,
 }
 
 /// The set of available commands for the `oober` CLI.
@@ -71,56 +102,7 @@ pub enum Commands {
     ///       pattern: 'AKIA[0-9A-Z]{16}'
     ///     - name: todo
     ///       pattern: 'TODO|FIXME|HACK'
-    Scan {
-        /// Path to the YAML file defining the scan patterns.
-        #[arg(short, long, default_value = "patterns.yaml")]
-        patterns: PathBuf,
-
-        /// An inline regex to scan for instead of a patterns file. Repeatable (-e A -e B).
-        /// When provided, the patterns file is ignored.
-        #[arg(short = 'e', long = "regex")]
-        regex: Vec<String>,
-
-        /// Path to the output file. If omitted, results are written to standard output.
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-
-        /// A comma-separated list of file extensions to include in the scan.
-        #[arg(short = 'x', long = "ext", value_delimiter = ',')]
-        extensions: Vec<String>,
-
-        /// The number of parallel worker threads to use. Defaults to the number of logical CPU cores.
-        #[arg(short = 'w', long = "workers", env = "UBER_SCANNER_WORKERS")]
-        workers: Option<usize>,
-
-        /// Enable incremental scanning. Only changed files will be re-scanned.
-        #[arg(long = "incremental", short = 'i')]
-        incremental: bool,
-
-        /// Force a full re-scan of all files, ignoring any cached results.
-        #[arg(long = "force-full")]
-        force_full: bool,
-
-        /// Clear the cache before starting the scan.
-        #[arg(long = "clear-cache")]
-        clear_cache: bool,
-
-        /// Use file content hashes for change detection instead of modification timestamps.
-        #[arg(long = "content-hash")]
-        content_hash: bool,
-
-        /// The output format for the scan results (e.g., `text`, `json`, `csv`, `sarif`, `html`).
-        #[arg(short = 'f', long = "format", default_value = "text")]
-        format: String,
-
-        /// Include a summary of scan statistics in the output.
-        #[arg(long = "summary")]
-        include_summary: bool,
-
-        /// The input files or directories to scan.
-        #[arg(required = true)]
-        inputs: Vec<PathBuf>,
-    },
+    Scan(Box<ScanArgs>),
 
     /// Replace patterns in files (with automatic backups)
     ///
@@ -138,65 +120,33 @@ pub enum Commands {
     ///       end: '/* DEBUG END */'
     ///
     /// Available presets: RemoveCopyright, CleanDebug, RemoveTodos, TrimWhitespace
-    Replace {
-        /// The name of a built-in preset to use for replacement.
-        #[arg(long, value_enum)]
-        preset: Option<Preset>,
-
-        /// Path to a YAML configuration file for replacement rules.
-        #[arg(short, long)]
-        config: Option<PathBuf>,
-
-        /// A single regex pattern to search for.
-        #[arg(short, long)]
-        pattern: Option<String>,
-
-        /// The string to replace the matched pattern with.
-        #[arg(short, long)]
-        replacement: Option<String>,
-
-        /// The directory to process.
-        #[arg(short, long, required = true)]
-        dir: PathBuf,
-
-        /// A comma-separated list of file extensions to include.
-        #[arg(short = 'x', long = "ext", value_delimiter = ',')]
-        extensions: Vec<String>,
-
-        /// A comma-separated list of directories to exclude.
-        #[arg(short = 'e', long = "exclude", value_delimiter = ',')]
-        exclude: Vec<String>,
-
-        /// Disable the creation of backup files (`.bak`).
-        #[arg(long)]
-        no_backup: bool,
-
-        /// Preview the changes without actually modifying any files.
-        #[arg(long)]
-        dry_run: bool,
-
-        /// Print each modified file (useful for audits; slower on large runs).
-        #[arg(short, long)]
-        verbose: bool,
-
-        /// The number of parallel worker threads to use.
-        #[arg(short, long)]
-        workers: Option<usize>,
-    },
+    Replace(Box<ReplaceArgs>),
 
     /// Restore files from backups (undo replacements)
     ///
     /// EXAMPLES:
     ///   ob undo -d .                    # Restore all files in current dir
     ///   ob undo -d src/ --keep-backups  # Restore but keep .bak files
+    ///   ob undo -d . --all              # Undo every timestamped run
+    ///   ob undo -d . --backup-dir .oober-backups  # Restore from a backup tree
     Undo {
         /// The directory where the `replace` operation was run.
         #[arg(short, long, required = true)]
         dir: PathBuf,
 
+        /// The `--backup-dir` that `replace` wrote its backups to. Sibling `.bak` files
+        /// are restored as well.
+        #[arg(long, value_name = "PATH")]
+        backup_dir: Option<PathBuf>,
+
         /// Keep the backup files after restoring the original files.
         #[arg(long)]
         keep_backups: bool,
+
+        /// Undo every run with `--timestamped-backups` at once, restoring each file's
+        /// oldest backup. By default only the most recent run is undone.
+        #[arg(long)]
+        all: bool,
     },
 
     /// Remove backup files without restoring
@@ -209,6 +159,10 @@ pub enum Commands {
         #[arg(short, long, required = true)]
         dir: PathBuf,
 
+        /// Also remove the backup tree that `replace --backup-dir` wrote.
+        #[arg(long, value_name = "PATH")]
+        backup_dir: Option<PathBuf>,
+
         /// Preview which backup files would be removed without deleting them.
         #[arg(long)]
         dry_run: bool,
@@ -220,33 +174,879 @@ pub enum Commands {
     ///   ob rename -d . -p 'test_(.*)' -r 'spec_$1'      # test_*.js -> spec_*.js
     ///   ob rename -d . -p '\\.tsx$' -r '.jsx' --dry-run  # Preview .tsx -> .jsx
     ///   ob rename -d . -p '(\\d+)_(.*)' -r '$2_$1'      # Reorder name parts
+    ///   ob rename -d . -p '\\.rs$' --add-prefix old_    # Prefix only .rs files
+    ///   ob rename -d . --strip-suffix .tmp              # a.csv.tmp -> a.csv
     ///
     /// Supports regex capture groups: $1, $2, etc.
-    Rename {
-        /// The directory containing files to rename.
-        #[arg(short, long, required = true)]
-        dir: PathBuf,
+    Rename(RenameArgs),
 
-        /// The regex pattern to match against filenames.
-        #[arg(short, long, required = true)]
-        pattern: String,
+    /// Re-render saved matches in another output format without re-scanning
+    ///
+    /// EXAMPLES:
+    ///   ob format --from results.jsonl --to report.html  # Format inferred from extension
+    ///   ob format --from results.jsonl -f sarif          # SARIF to stdout
+    ///
+    /// The input has one JSON match per line, e.g.:
+    ///   {"pattern_name":"todo","file_path":"src/a.rs","line_number":3,"line_content":"// TODO"}
+    Format(FormatArgs),
 
-        /// The replacement string. Can include capture groups from the pattern (e.g., `$1`).
-        #[arg(short, long, required = true)]
-        replacement: String,
+    /// Measure scan throughput by running the same scan several times
+    ///
+    /// EXAMPLES:
+    ///   ob bench -d . -p patterns.yaml              # 5 timed runs after 1 warmup
+    ///   ob bench -d src/ -p patterns.yaml -w 4 -n 10
+    ///
+    /// Reports the median files/sec and MB/sec, with the min and max across runs.
+    Bench(BenchArgs),
 
-        /// Preview the renames without actually renaming any files.
-        #[arg(long)]
-        dry_run: bool,
+    /// Explain what a regex matches, component by component
+    ///
+    /// EXAMPLES:
+    ///   ob explain 'AKIA[0-9A-Z]{16}'
+    ///   ob explain '(TODO|FIXME):\s*' --sample '// TODO: later'
+    Explain(ExplainArgs),
 
-        /// Print each renamed file (slower on large runs).
-        #[arg(short, long)]
-        verbose: bool,
+    /// Find lines (or blocks of lines) that are repeated across files
+    ///
+    /// EXAMPLES:
+    ///   ob duplicates -d .                          # Lines that appear 2+ times
+    ///   ob duplicates -d src/ -x rs --block 3       # Repeated 3-line blocks
+    ///   ob duplicates -d . --min-count 5 --min-length 30
+    ///
+    /// Lines are compared with surrounding whitespace trimmed and inner runs of
+    /// whitespace collapsed. Short lines such as `}` are ignored (see --min-length).
+    Duplicates(DuplicatesArgs),
 
-        /// The number of parallel worker threads to use.
-        #[arg(short, long)]
-        workers: Option<usize>,
-    },
+    /// List the file extensions in a tree, with file counts and total sizes
+    ///
+    /// EXAMPLES:
+    ///   ob extensions -d .                  # Pick the extensions to pass to -x
+    ///
+    /// The tree is walked like `scan` walks it, so ignored and hidden files are left out.
+    /// Extensions are lowercased; files without one are listed as `(none)`.
+    Extensions(ExtensionsArgs),
+
+    /// Combine the JSON results of several scans into one report
+    ///
+    /// EXAMPLES:
+    ///   ob merge shard1.json shard2.json -o combined.json
+    ///   ob merge shard*.json -o report.html              # Format inferred from extension
+    ///
+    /// Inputs are `scan -f json` reports (or JSON arrays of matches). Matches found in
+    /// more than one input (same pattern, file, line and content) are kept once.
+    Merge(MergeArgs),
+}
+
+impl Commands {
+    /// Returns how many times `-v` was passed to the command, for log level selection.
+    pub fn verbosity(&self) -> u8 {
+        match self {
+            Commands::Scan(args) => args.verbose,
+            Commands::Replace(args) => args.verbose,
+            Commands::Rename(args) => args.verbose,
+            Commands::Undo { .. }
+            | Commands::CleanBackups { .. }
+            | Commands::Format(_)
+            | Commands::Bench(_)
+            | Commands::Explain(_)
+            | Commands::Duplicates(_)
+            | Commands::Extensions(_)
+            | Commands::Merge(_) => 0,
+        }
+    }
+}
+
+/// Arguments for the `scan` command.
+#[derive(clap::Args, Debug)]
+#[command(group(
+    clap::ArgGroup::new("summary_output")
+        .multiple(true)
+        .args(["include_summary", "summary_only"])
+))]
+pub struct ScanArgs {
+    /// Path to the YAML file defining the scan patterns.
+    #[arg(short, long, default_value = "patterns.yaml")]
+    pub patterns: PathBuf,
+
+    /// Load and merge every `.yaml`/`.yml` patterns file in this directory (e.g. pattern
+    /// packs) instead of a single patterns file. A pattern name defined twice keeps its
+    /// first definition, in file name order.
+    #[arg(long, value_name = "DIR", conflicts_with = "patterns")]
+    pub patterns_dir: Option<PathBuf>,
+
+    /// An inline regex to scan for instead of a patterns file. Repeatable (-e A -e B).
+    /// When provided, the patterns file is ignored.
+    #[arg(short = 'e', long = "regex")]
+    pub regex: Vec<String>,
+
+    /// Path to the output file. If omitted, results are written to standard output.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// A comma-separated list of file extensions to include in the scan.
+    #[arg(short = 'x', long = "ext", value_delimiter = ',')]
+    pub extensions: Vec<String>,
+
+    /// A comma-separated list of exact file names (e.g. `Makefile,Dockerfile`) to scan
+    /// in addition to the `--ext` filter. Useful for files without an extension.
+    #[arg(long = "filename", value_delimiter = ',')]
+    pub filenames: Vec<String>,
+
+    /// The number of parallel worker threads to use. Defaults to the number of logical CPU cores.
+    #[arg(short = 'w', long = "workers", env = "UBER_SCANNER_WORKERS")]
+    pub workers: Option<usize>,
+
+    /// The most files to have open for reading at once, independently of `--workers`.
+    /// Lower it on network storage, or to stay under the open file limit; workers beyond
+    /// it wait for a file to finish. Unlimited by default.
+    #[arg(long, value_name = "N")]
+    pub io_concurrency: Option<usize>,
+
+    /// Enable incremental scanning. Only changed files will be re-scanned.
+    #[arg(long = "incremental", short = 'i')]
+    pub incremental: bool,
+
+    /// Force a full re-scan of all files, ignoring any cached results.
+    #[arg(long = "force-full")]
+    pub force_full: bool,
+
+    /// Clear the cache before starting the scan.
+    #[arg(long = "clear-cache")]
+    pub clear_cache: bool,
+
+    /// With `--incremental`, detect changed files by a SHA-256 hash of their contents
+    /// instead of their size and modification time. Slower, since every file is read in
+    /// full on each run, but it catches edits that keep the size and restore the
+    /// timestamp, and ignores files that were only touched.
+    #[arg(long = "content-hash", requires = "incremental")]
+    pub content_hash: bool,
+
+    /// The output format for the scan results (e.g., `text`, `json`, `csv`, `sarif`, `html`).
+    #[arg(short = 'f', long = "format", default_value = "text")]
+    pub format: String,
+
+    /// With `--format csv`: emit one row per match (`matches`), or one
+    /// `Pattern,Count,FileCount` row per pattern (`summary`).
+    #[arg(long, value_enum, default_value_t = CsvMode::Matches)]
+    pub csv_mode: CsvMode,
+
+    /// Write `json` and `sarif` output on a single line instead of pretty-printed.
+    /// Smaller and faster for large scans piped to other tools.
+    #[arg(long)]
+    pub compact: bool,
+
+    /// Sort the matches by file path (`path`, the default), or list the files with the
+    /// most matches first (`count`).
+    #[arg(long, value_enum, value_name = "ORDER")]
+    pub sort_by: Option<SortBy>,
+
+    /// Write the matches in whatever order the workers finish, without sorting them.
+    /// Sorting, the default, holds back all output until the scan finishes; with this
+    /// flag, text output is streamed as files are scanned instead. Faster for large
+    /// scans, but the order varies between runs.
+    #[arg(long, conflicts_with = "sort_by")]
+    pub no_sort: bool,
+
+    /// Only write the first N matches (after `--sort-by`), to sample a large result.
+    /// The whole scan still runs; the output notes how many matches were left out.
+    #[arg(long, value_name = "N")]
+    pub head: Option<usize>,
+
+    /// Also write the matched text of each pattern to `<DIR>/<pattern>.txt`, one match
+    /// per line, ordered by file, line and column. The regular output is unchanged.
+    #[arg(long, value_name = "DIR")]
+    pub split_by_pattern: Option<PathBuf>,
+
+    /// With context lines (`-A`/`-B`/`-C`), the line written between groups of lines
+    /// that are not adjacent in `text` output.
+    #[arg(long, value_name = "SEP", default_value = "--")]
+    pub group_separator: String,
+
+    /// With context lines, write nothing between groups of lines that are not adjacent.
+    #[arg(long, conflicts_with = "group_separator")]
+    pub no_group_separator: bool,
+
+    /// With context lines, prefix each matching line of `text` output with this marker
+    /// (e.g. `> `) to set it apart from the context lines.
+    #[arg(long, value_name = "PREFIX", default_value = "")]
+    pub match_marker: String,
+
+    /// Write file paths with `/` separators on every platform (e.g. on Windows), for
+    /// tools and golden files that expect them. SARIF URIs always use `/`.
+    #[arg(long)]
+    pub posix_paths: bool,
+
+    /// Include a summary of scan statistics (totals, top patterns, counts per file
+    /// extension) in the output. Supported by the `text` and `json` formats.
+    #[arg(long = "summary")]
+    pub include_summary: bool,
+
+    /// Only write the summary, without the individual matches: the text summary, a
+    /// JSON object of totals, or the `--csv-mode summary` rows. Not supported by the
+    /// `sarif` and `html` formats.
+    #[arg(long)]
+    pub summary_only: bool,
+
+    /// How many patterns the summary lists under "Top patterns": a number, or `all`.
+    #[arg(long, value_name = "N|all", default_value = "10", requires = "summary_output")]
+    pub summary_top: SummaryTop,
+
+    /// Increase diagnostic output on stderr (`-v` info, `-vv` per-file debug, `-vvv` trace).
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Only match whole words: each pattern is wrapped in word boundaries. With
+    /// `--fixed-strings` the boundaries go around the escaped literal.
+    #[arg(short = 'W', long)]
+    pub word: bool,
+
+    /// Use Unicode word boundaries for `--word`, so identifiers like `café_token`
+    /// are treated as single words. By default boundaries are ASCII-only.
+    #[arg(long = "word-unicode", requires = "word")]
+    pub word_unicode: bool,
+
+    /// Also flag high-entropy tokens (likely secrets) whose Shannon entropy in bits per
+    /// character exceeds this threshold, reported as `high_entropy`. Only tokens of 20+
+    /// characters are considered. Values around 4.0-4.5 work well for base64-like keys.
+    #[arg(long, value_name = "THRESHOLD")]
+    pub entropy: Option<f64>,
+
+    /// Stop recording matches for a file once it has this many. Useful for generated
+    /// files that would otherwise flood the output. Capped files are reported on stderr.
+    #[arg(long = "max-per-file", value_name = "N")]
+    pub max_per_file: Option<usize>,
+
+    /// Stop recording matches of each pattern in a file once it has this many, so one
+    /// noisy pattern cannot flood the output. Capped patterns are noted in the summary.
+    #[arg(long, value_name = "N")]
+    pub max_count: Option<usize>,
+
+    /// Drop matches that only occur inside comments and/or string literals
+    /// (comma-separated: `comment,string`). Uses a simple lexer chosen by file extension
+    /// (C-like, Rust, Python, shell/YAML, SQL); other files are not filtered.
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "CONTEXTS")]
+    pub exclude_context: Vec<CodeContext>,
+
+    /// Compile patterns in multi-line mode (as if prefixed with `(?m)`), like the
+    /// `multiline_anchors` config option. Each line is matched on its own, so `^` and `$`
+    /// already match at line boundaries; this keeps patterns consistent with `replace`.
+    #[arg(long)]
+    pub multiline_anchors: bool,
+
+    /// Match every pattern case-insensitively, without adding `(?i)` to each one.
+    #[arg(short = 'I', long)]
+    pub ignore_case: bool,
+
+    /// Treat the patterns (from `-e` or the patterns file) as literal strings instead of
+    /// regexes, so `a.b.c` or `C:\path` need no escaping.
+    #[arg(short = 'F', long)]
+    pub fixed_strings: bool,
+
+    /// Report the lines that match none of the patterns instead of the matches, as
+    /// `no-match` results (e.g. to find lines missing a required header or marker).
+    /// A line whose hits are all dropped by `exclude_if` or `--exclude-context` counts
+    /// as not matching.
+    #[arg(long, conflicts_with_all = ["after_context", "before_context", "context"])]
+    pub invert: bool,
+
+    /// Report each text file with no match at all, as one `no-match` result on line 0,
+    /// instead of the matches. Scanning a file stops at its first match.
+    #[arg(long, conflicts_with_all = ["invert", "after_context", "before_context", "context"])]
+    pub files_without_match: bool,
+
+    /// Match each pattern against the whole content of each file instead of line by line,
+    /// for document-level checks on e.g. a JSON or YAML file. Each pattern is reported at
+    /// most once per file, on line 1 with the first line of its match as the content.
+    /// Use `\n` or `(?s)` in a pattern to match across lines.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "invert", "files_without_match", "after_context", "before_context", "context",
+            "byte_range", "entropy", "exclude_context", "hex_content",
+        ]
+    )]
+    pub whole_file: bool,
+
+    /// Only scan files of at least this size (e.g. `512K`, `10M`; a bare number is
+    /// bytes). Smaller files are left out when the files are collected.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub min_filesize: Option<u64>,
+
+    /// Skip files larger than this size without reading them (e.g. `512K`, `10M`, `2G`;
+    /// a bare number is bytes). Larger files are left out when the files are collected,
+    /// and skipped files are counted in the summary.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_filesize: Option<u64>,
+
+    /// Only scan the lines that start within this byte range of each file, as
+    /// `START:END` with sizes like `--max-filesize` (`END` may be omitted for the rest of
+    /// the file). A line cut by `START` belongs to the previous range, so consecutive
+    /// ranges cover each line once. Line numbers still count from the start of the file.
+    #[arg(long, value_name = "START:END", value_parser = parse_byte_range)]
+    pub byte_range: Option<Range<u64>>,
+
+    /// The text encoding of the scanned files. `auto` decodes files that start with a
+    /// UTF-8 or UTF-16 byte order mark (e.g. UTF-16LE files written by Windows tools)
+    /// and reads the rest as UTF-8. Transcoded files are matched as UTF-8, so columns
+    /// and `--byte-range` offsets refer to the decoded text.
+    #[arg(long, value_enum, value_name = "ENCODING", default_value_t = TextEncoding::Auto)]
+    pub encoding: TextEncoding,
+
+    /// Report the content of matched (and context) lines as a hex dump of their bytes,
+    /// e.g. `6b 65 79 3d ff`, instead of text in which invalid UTF-8 is replaced. For
+    /// files with some binary content; patterns still match the text.
+    #[arg(long)]
+    pub hex_content: bool,
+
+    /// Stop the scan once it has run this long (e.g. `90s`, `5m`, `500ms`; a bare number
+    /// is seconds). Files not yet started are skipped, the matches found so far are
+    /// written, and a warning reports the scan as partial.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Resolve relative input and patterns-file paths against this directory instead of
+    /// the current working directory.
+    #[arg(long, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Scan only the files git tracks under each input directory (via `git ls-files`),
+    /// skipping untracked files even when they are not gitignored.
+    #[arg(long)]
+    pub tracked_only: bool,
+
+    /// Include hidden files and directories (names starting with `.`), e.g. `.github/`
+    /// or `.env`.
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// Don't skip the paths excluded by `.gitignore`, `.ignore`, `.ooberignore` and git's
+    /// other exclude files. The `ignore_patterns` of the patterns file still apply.
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Descend into symlinked directories. Symlinked files are always scanned. A link
+    /// back into its own ancestors is reported and skipped rather than walked forever,
+    /// but links can still lead outside the inputs or visit a directory more than once.
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Keep duplicate results. By default a file listed by several (overlapping) inputs
+    /// is scanned once, and repeated matches (same pattern, file, line and column) are
+    /// written once.
+    #[arg(long)]
+    pub no_dedupe: bool,
+
+    /// Skip files that cannot be read (e.g. for lack of permission) instead of failing
+    /// the whole scan. The skipped files are listed on stderr at the end.
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Show a progress bar on stderr while the files are scanned. It is hidden when
+    /// stderr is not a terminal.
+    #[arg(long, conflicts_with = "keep_going")]
+    pub progress: bool,
+
+    /// After the scan, print to stderr how long the files of each directory took to scan,
+    /// as folded stacks (`src;nested 1520`, in microseconds) that flamegraph tools such
+    /// as `inferno-flamegraph` read. Times are summed over the workers, so their total
+    /// can exceed the wall time.
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Only list how many files the scan would read and their total size, without
+    /// opening them. Useful for checking filters before a large scan.
+    #[arg(long)]
+    pub count_files: bool,
+
+    /// Show this many lines of context after each match, like `grep -A`. Included by
+    /// the `text` and `json` formats.
+    #[arg(short = 'A', long, value_name = "N")]
+    pub after_context: Option<usize>,
+
+    /// Show this many lines of context before each match, like `grep -B`.
+    #[arg(short = 'B', long, value_name = "N")]
+    pub before_context: Option<usize>,
+
+    /// Show this many lines of context before and after each match, like `grep -C`.
+    /// `-A` and `-B` take precedence.
+    #[arg(short = 'C', long, value_name = "N")]
+    pub context: Option<usize>,
+
+    /// Annotate each match with the author and commit that last changed its line, via
+    /// `git blame` (shown in `json` output). Files outside a git repository, untracked
+    /// files and uncommitted lines are left without an author.
+    #[arg(long)]
+    pub blame: bool,
+
+    /// Only scan with the patterns of these names (comma-separated).
+    #[arg(long, value_delimiter = ',', value_name = "NAMES")]
+    pub only: Vec<String>,
+
+    /// Leave out the patterns of these names (comma-separated).
+    #[arg(long, value_delimiter = ',', value_name = "NAMES")]
+    pub skip: Vec<String>,
+
+    /// Warn about patterns that did not match anything during the scan.
+    #[arg(long)]
+    pub warn_unused_patterns: bool,
+
+    /// Fail (after writing the results) if any pattern did not match anything.
+    #[arg(long)]
+    pub error_unused_patterns: bool,
+
+    /// The input files or directories to scan. `-` reads standard input, reported as
+    /// `<stdin>`; with no inputs, standard input is scanned if it is not a terminal.
+    pub inputs: Vec<PathBuf>,
+}
+
+/// Arguments for the `replace` command.
+#[derive(clap::Args, Debug)]
+pub struct ReplaceArgs {
+    /// The name of a built-in preset to use for replacement.
+    #[arg(long, value_enum)]
+    pub preset: Option<Preset>,
+
+    /// Path to a YAML configuration file for replacement rules.
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// A single regex pattern to search for.
+    #[arg(short, long)]
+    pub pattern: Option<String>,
+
+    /// The string to replace the matched pattern with. Capture groups can be referenced
+    /// as `$1` or `${name}`, so `-p '(key=)\w+' -r '${1}REDACTED'` rewrites only the
+    /// value and keeps the prefix. Use `${1}` rather than `$1` when letters follow.
+    #[arg(short, long)]
+    pub replacement: Option<String>,
+
+    /// Read the replacement string from this file instead of `-r`, e.g. to insert a
+    /// multi-line license header. The contents are used verbatim, trailing newline
+    /// included; `$` references work as with `-r` (write `$$` for a literal `$`).
+    #[arg(long, value_name = "PATH", conflicts_with = "replacement")]
+    pub replacement_file: Option<PathBuf>,
+
+    /// Pipe each match of `-p` through this shell command and use its stdout as the
+    /// replacement, e.g. `--replace-cmd 'jq -c .'`. A trailing newline added by the
+    /// command is dropped. If the command fails, the file is left unchanged. Commands run
+    /// one at a time per worker, so at most `--workers` run at once.
+    #[arg(
+        long,
+        value_name = "CMD",
+        requires = "pattern",
+        conflicts_with_all = ["replacement", "replacement_file"]
+    )]
+    pub replace_cmd: Option<String>,
+
+    /// Insert this text before each match of `-p`, keeping the match itself (e.g.
+    /// `--wrap-before '<<' --wrap-after '>>'` turns `secret` into `<<secret>>`). The text
+    /// is literal: `$` needs no escaping.
+    #[arg(
+        long,
+        value_name = "TEXT",
+        requires = "pattern",
+        conflicts_with_all = ["replacement", "replacement_file", "replace_cmd"]
+    )]
+    pub wrap_before: Option<String>,
+
+    /// Insert this text after each match of `-p`, like `--wrap-before`.
+    #[arg(
+        long,
+        value_name = "TEXT",
+        requires = "pattern",
+        conflicts_with_all = ["replacement", "replacement_file", "replace_cmd"]
+    )]
+    pub wrap_after: Option<String>,
+
+    /// Make `^` and `$` match at the start and end of every line instead of only at the
+    /// start and end of the file, like the `multiline_anchors` config option.
+    #[arg(long)]
+    pub multiline_anchors: bool,
+
+    /// Treat the search patterns (from `-p`, `--config` or a preset) as literal strings
+    /// instead of regexes. Block markers are always literal.
+    #[arg(short = 'F', long)]
+    pub fixed_strings: bool,
+
+    /// Convert every line ending of the processed files to this style once the patterns
+    /// have been applied, e.g. with `--preset NormalizeLineEndings` (which defaults to
+    /// `lf`). Each converted line ending counts as a change.
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub line_endings: Option<LineEnding>,
+
+    /// The directory to process.
+    #[arg(short, long, required = true)]
+    pub dir: PathBuf,
+
+    /// A comma-separated list of file extensions to include.
+    #[arg(short = 'x', long = "ext", value_delimiter = ',')]
+    pub extensions: Vec<String>,
+
+    /// A comma-separated list of directories to exclude. They are excluded on top of the
+    /// ignore files, so a `!` rule in `.gitignore` or `.ooberignore` cannot bring them back.
+    #[arg(short = 'e', long = "exclude", value_delimiter = ',')]
+    pub exclude: Vec<String>,
+
+    /// Include hidden files and directories (names starting with `.`), e.g. `.github/`
+    /// or `.env`. Files inside `.git` are never modified.
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// Don't skip the paths excluded by `.gitignore`, `.ignore`, `.ooberignore` and git's
+    /// other exclude files. The `ignore_patterns` of the config file still apply.
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Descend into symlinked directories. Symlinked files are always processed. A link
+    /// back into its own ancestors is reported and skipped rather than walked forever,
+    /// but links can still lead outside `--dir` or visit a directory more than once.
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Disable the creation of backup files (`.bak`).
+    #[arg(long)]
+    pub no_backup: bool,
+
+    /// Write backups under this directory, mirroring the paths relative to `--dir`
+    /// (`.oober-backups/src/foo.rs`), instead of as `.bak` files next to each file.
+    /// Pass the same path to `undo` and `clean-backups`. Relative paths are resolved
+    /// like `--dir`.
+    #[arg(long, value_name = "PATH", conflicts_with = "no_backup")]
+    pub backup_dir: Option<PathBuf>,
+
+    /// Name backups after the time of the run, to the microsecond and in UTC
+    /// (`foo.rs.2024-06-01T12-00-00-250000.bak`), so a later run does not overwrite them.
+    /// `undo` restores the most recent one.
+    #[arg(long, conflicts_with_all = ["no_backup", "backup_dir"])]
+    pub timestamped_backups: bool,
+
+    /// Preview the changes without actually modifying any files. With text output, a
+    /// unified diff of each change is printed.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print a unified diff of each change before writing it, as `--dry-run` does.
+    /// Removed and added lines are colored when stdout is a terminal.
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Print each modified file (useful for audits; slower on large runs).
+    /// Repeat for diagnostic output on stderr (`-vv` per-file debug, `-vvv` trace).
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// The number of parallel worker threads to use.
+    #[arg(short, long)]
+    pub workers: Option<usize>,
+
+    /// A shell command to run on each modified file after it is written. `{}` is
+    /// replaced with the file path (e.g. `rustfmt {}`). Failures are reported as warnings.
+    #[arg(long = "on-change", value_name = "CMD")]
+    pub on_change: Option<String>,
+
+    /// Rewrite files with multiple hardlinks in place so all links see the change.
+    /// This is not atomic: an interrupted write can leave the file truncated. Without
+    /// this flag such files are replaced atomically, which detaches them from their links.
+    #[arg(long)]
+    pub inplace_hardlinks: bool,
+
+    /// Resolve a relative `--dir` and `--config` against this directory instead of the
+    /// current working directory.
+    #[arg(long, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// How to report the results: a text summary, a JSON report listing each changed
+    /// file with its per-pattern change counts, SARIF with suggested fixes, or GitHub
+    /// ```suggestion blocks for a review bot to post. Combine with `--dry-run` to preview
+    /// the changes without applying them (`github-suggestions` requires it).
+    #[arg(short = 'f', long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+
+    /// Only apply patterns to lines that also match this regex (e.g. `'^import'` to
+    /// rewrite only import lines). Patterns are then matched line by line.
+    #[arg(long, value_name = "REGEX")]
+    pub line_filter: Option<String>,
+
+    /// Re-apply all patterns until the content stops changing (at most 10 passes
+    /// unless `--max-passes` is given). Useful for collapsing nested matches.
+    #[arg(long)]
+    pub repeat: bool,
+
+    /// The maximum number of passes for `--repeat` (implies `--repeat`).
+    #[arg(long, value_name = "N")]
+    pub max_passes: Option<usize>,
+
+    /// Restore each rewritten file's owner and group (unix). Rewrites create a new
+    /// file owned by the current user; restoring usually needs root. Failures are warnings.
+    #[arg(long)]
+    pub preserve_owner: bool,
+
+    /// Skip files that had nothing to replace in the last `--incremental` run and have
+    /// not changed since. The cache is discarded whenever the replacement rules change.
+    #[arg(long)]
+    pub incremental: bool,
+
+    /// With `--incremental`, detect changes by content hash instead of modification time.
+    #[arg(long, requires = "incremental")]
+    pub content_hash: bool,
+
+    /// Refuse to run when `--dir` is inside a git repository with uncommitted changes
+    /// (including untracked files) under it, so rewrites are never mixed with unsaved
+    /// work. Directories outside a repository are allowed, but if git cannot be run or
+    /// fails, the replace is refused too. Dry runs are not checked.
+    #[arg(long)]
+    pub require_clean_tree: bool,
+
+    /// Run even if `--require-clean-tree` finds uncommitted changes.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Only replace the Nth match (counting from 1) of each pattern in each file,
+    /// leaving the other matches intact. Every pattern needs a replacement.
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["line_filter", "replace_cmd", "repeat", "max_passes"]
+    )]
+    pub nth: Option<usize>,
+}
+
+/// Arguments for the `rename` command.
+#[derive(clap::Args, Debug)]
+#[command(group(
+    clap::ArgGroup::new("operation")
+        .required(true)
+        .multiple(true)
+        .args(["replacement", "add_prefix", "add_suffix", "strip_prefix", "strip_suffix"])
+))]
+pub struct RenameArgs {
+    /// The directory containing files to rename.
+    #[arg(short, long, required = true)]
+    pub dir: PathBuf,
+
+    /// The regex pattern to match against filenames. Without `-r`, it only selects
+    /// which files the prefix/suffix options apply to.
+    #[arg(short, long)]
+    pub pattern: Option<String>,
+
+    /// The replacement string. Can include capture groups from the pattern (e.g., `$1`).
+    #[arg(short, long, requires = "pattern")]
+    pub replacement: Option<String>,
+
+    /// Add this text to the start of each file name.
+    #[arg(long, value_name = "TEXT")]
+    pub add_prefix: Option<String>,
+
+    /// Add this text to the end of each file name (after any extension).
+    #[arg(long, value_name = "TEXT")]
+    pub add_suffix: Option<String>,
+
+    /// Remove this text from the start of file names that begin with it.
+    #[arg(long, value_name = "TEXT")]
+    pub strip_prefix: Option<String>,
+
+    /// Remove this text from the end of file names that end with it (e.g. `.tmp`).
+    #[arg(long, value_name = "TEXT")]
+    pub strip_suffix: Option<String>,
+
+    /// Preview the renames without actually renaming any files.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print each renamed file (slower on large runs).
+    /// Repeat for diagnostic output on stderr (`-vv` per-file debug, `-vvv` trace).
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// The number of parallel worker threads to use.
+    #[arg(short, long)]
+    pub workers: Option<usize>,
+
+    /// Also rename directories whose names match. Directories are renamed after
+    /// files, deepest first, so renaming a parent never invalidates a child's path.
+    #[arg(long)]
+    pub include_dirs: bool,
+
+    /// Include hidden files and directories (names starting with `.`), e.g. `.github/`
+    /// or `.env`. Nothing inside `.git` is renamed.
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// Don't skip the paths excluded by `.gitignore`, `.ignore`, `.ooberignore` and git's
+    /// other exclude files.
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Descend into symlinked directories. Symlinked files are always renamed. A link
+    /// back into its own ancestors is reported and skipped rather than walked forever,
+    /// but links can still lead outside `--dir` or visit a directory more than once.
+    #[arg(long)]
+    pub follow_symlinks: bool,
+}
+
+/// Arguments for the `format` command.
+#[derive(clap::Args, Debug)]
+pub struct FormatArgs {
+    /// A JSON Lines file with one match per line.
+    #[arg(long, value_name = "FILE")]
+    pub from: PathBuf,
+
+    /// Output file (default: stdout).
+    #[arg(long, value_name = "FILE")]
+    pub to: Option<PathBuf>,
+
+    /// The output format (`text`, `json`, `csv`, `sarif`, `html`). Defaults to the
+    /// extension of `--to`, or `text`.
+    #[arg(short = 'f', long = "format")]
+    pub format: Option<String>,
+
+    /// Include a summary of the matches in the output (`text` and `json` formats only).
+    #[arg(long = "summary")]
+    pub include_summary: bool,
+}
+
+/// Arguments for the `merge` command.
+#[derive(clap::Args, Debug)]
+pub struct MergeArgs {
+    /// The JSON result files to merge.
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Output file (default: stdout).
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// The output format (`text`, `json`, `csv`, `sarif`, `html`). Defaults to the
+    /// extension of `--output`, or `json`.
+    #[arg(short = 'f', long = "format")]
+    pub format: Option<String>,
+
+    /// Include a summary of the matches in the output (`text` and `json` formats only).
+    #[arg(long = "summary")]
+    pub include_summary: bool,
+}
+
+/// Arguments for the `bench` command.
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    /// The directory to scan.
+    #[arg(short, long, required = true)]
+    pub dir: PathBuf,
+
+    /// Path to the YAML file defining the scan patterns.
+    #[arg(short, long, default_value = "patterns.yaml")]
+    pub patterns: PathBuf,
+
+    /// A comma-separated list of file extensions to include in the scan.
+    #[arg(short = 'x', long = "ext", value_delimiter = ',')]
+    pub extensions: Vec<String>,
+
+    /// The number of parallel worker threads to use. Defaults to the number of logical CPU cores.
+    #[arg(short = 'w', long = "workers", env = "UBER_SCANNER_WORKERS")]
+    pub workers: Option<usize>,
+
+    /// The number of timed scans.
+    #[arg(short = 'n', long, default_value_t = 5)]
+    pub iterations: usize,
+
+    /// The number of untimed scans run first to warm the file system cache.
+    #[arg(long, default_value_t = 1)]
+    pub warmup: usize,
+}
+
+/// Arguments for the `explain` command.
+#[derive(clap::Args, Debug)]
+pub struct ExplainArgs {
+    /// The regex to explain.
+    pub pattern: String,
+
+    /// Text to run the regex against; each match is marked under it.
+    #[arg(long, value_name = "TEXT")]
+    pub sample: Option<String>,
+}
+
+/// Arguments for the `duplicates` command.
+#[derive(clap::Args, Debug)]
+pub struct DuplicatesArgs {
+    /// The directory to search.
+    #[arg(short, long, required = true)]
+    pub dir: PathBuf,
+
+    /// A comma-separated list of file extensions to include.
+    #[arg(short = 'x', long = "ext", value_delimiter = ',')]
+    pub extensions: Vec<String>,
+
+    /// Report lines that occur at least this many times.
+    #[arg(long, default_value_t = 2)]
+    pub min_count: usize,
+
+    /// Ignore lines (or blocks) shorter than this many characters after normalization.
+    #[arg(long, default_value_t = 10)]
+    pub min_length: usize,
+
+    /// Compare runs of this many consecutive non-blank lines instead of single lines.
+    #[arg(long, value_name = "LINES", default_value_t = 1)]
+    pub block: usize,
+
+    /// The number of parallel worker threads to use. Defaults to the number of logical CPU cores.
+    #[arg(short = 'w', long = "workers", env = "UBER_SCANNER_WORKERS")]
+    pub workers: Option<usize>,
+}
+
+/// Arguments for the `extensions` command.
+#[derive(clap::Args, Debug)]
+pub struct ExtensionsArgs {
+    /// The directory to walk.
+    #[arg(short, long, required = true)]
+    pub dir: PathBuf,
+}
+
+/// Parses a duration such as `500ms`, `30s`, `5m` or `1h`. A bare number is seconds.
+fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("expected a duration like `30s` or `5m`, got `{s}`"))?;
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return Err(format!("unknown duration unit `{unit}` (use ms, s, m or h)")),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration `{s}`: {e}"))
+}
+
+/// Parses a size such as `4096`, `512K`, `10M` or `2G`, in powers of 1024. A bare
+/// number is bytes.
+fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("expected a size like `512K` or `10M`, got `{s}`"))?;
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("unknown size unit `{unit}` (use K, M or G)")),
+    };
+    Ok((value * multiplier as f64) as u64)
+}
+
+/// Parses a byte range such as `0:64M` or `1G:` (to the end of the file).
+fn parse_byte_range(s: &str) -> std::result::Result<Range<u64>, String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected a range like `0:64M`, got `{s}`"))?;
+    let start = parse_size(start)?;
+    let end = if end.is_empty() { u64::MAX } else { parse_size(end)? };
+    if start > end {
+        return Err(format!("the range `{s}` ends before it starts"));
+    }
+    Ok(start..end)
 }
 
 /// Parses command-line arguments and returns the populated `Args` struct.
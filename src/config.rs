@@ -1,5 +1,10 @@
 use crate::errors::{Error, Result};
+use ignore::gitignore::GitignoreBuilder;
+use ignore::WalkBuilder;
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::path::{Path, PathBuf};
@@ -11,6 +16,31 @@ pub struct Pattern {
     pub name: String,
     /// The regex pattern string.
     pub pattern: String,
+    /// Named capture groups of `pattern` to report with each match (e.g. `level`,
+    /// `message`), as extra columns in the JSON and CSV output.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// A regex that vetoes a match: lines it matches are not reported for this pattern
+    /// (e.g. `EXAMPLE` to skip documented sample keys).
+    #[serde(default)]
+    pub exclude_if: Option<String>,
+    /// Regex flags for this pattern alone, as letters (e.g. `im`): `i` case-insensitive,
+    /// `m` multi-line anchors, `s` `.` matches newlines, `x` verbose, `U` lazy by default.
+    #[serde(default)]
+    pub flags: Option<String>,
+}
+
+impl Pattern {
+    /// Creates a pattern without any reported fields.
+    pub fn new(name: impl Into<String>, pattern: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            pattern: pattern.into(),
+            fields: Vec::new(),
+            exclude_if: None,
+            flags: None,
+        }
+    }
 }
 
 /// Configuration for the scan operation.
@@ -18,6 +48,23 @@ pub struct Pattern {
 pub struct ScanConfig {
     /// A list of patterns to scan for.
     pub patterns: Vec<Pattern>,
+    /// Paths to skip, written as gitignore rules relative to each scanned directory.
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+    /// Compile patterns in multi-line mode, so `^` and `$` match at line boundaries.
+    /// Scans already match each line on its own, so this only changes how patterns
+    /// behave when they are shared with `replace`.
+    #[serde(default)]
+    pub multiline_anchors: bool,
+}
+
+impl ScanConfig {
+    /// Appends the patterns and ignore rules of `other`, a later document of the same file.
+    fn merge(&mut self, other: ScanConfig) {
+        self.patterns.extend(other.patterns);
+        self.ignore_patterns.extend(other.ignore_patterns);
+        self.multiline_anchors |= other.multiline_anchors;
+    }
 }
 
 /// Configuration for the replace operation.
@@ -28,6 +75,10 @@ pub struct ReplaceConfig {
     /// A list of replacement strings. Each element corresponds to a pattern.
     /// `None` can be used to indicate no replacement for a given pattern.
     pub replacements: Vec<Option<String>>,
+    /// The most times each pattern is applied per file, paired with `patterns` like
+    /// `replacements`. `None`, or a missing element, means no limit.
+    #[serde(default)]
+    pub limits: Vec<Option<usize>>,
     /// A list of blocks to ignore during replacement.
     #[serde(default)]
     pub blocks: Vec<Block>,
@@ -37,6 +88,54 @@ pub struct ReplaceConfig {
     /// An optional list of file or directory paths to exclude from the operation.
     #[serde(default)]
     pub exclude: Option<Vec<String>>,
+    /// Paths to skip, written as gitignore rules relative to the processed directory.
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+    /// Compile patterns in multi-line mode, so `^` and `$` match at the start and end
+    /// of every line instead of only at the start and end of the file.
+    #[serde(default)]
+    pub multiline_anchors: bool,
+    /// If set, every line ending of the processed files is converted to this style,
+    /// after the patterns are applied.
+    #[serde(default)]
+    pub line_endings: Option<LineEnding>,
+}
+
+/// A style of line ending that `replace` can normalize files to.
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// `\n`, as on Unix.
+    Lf,
+    /// `\r\n`, as on Windows.
+    Crlf,
+}
+
+impl ReplaceConfig {
+    /// Appends the patterns, blocks and filters of `other`, a later document of the same
+    /// file. Each document's patterns stay paired with its own replacements.
+    fn merge(&mut self, mut other: ReplaceConfig) {
+        self.replacements.resize(self.patterns.len(), None);
+        other.replacements.resize(other.patterns.len(), None);
+        self.limits.resize(self.patterns.len(), None);
+        other.limits.resize(other.patterns.len(), None);
+        self.patterns.extend(other.patterns);
+        self.replacements.extend(other.replacements);
+        self.limits.extend(other.limits);
+        self.blocks.extend(other.blocks);
+        merge_list(&mut self.extensions, other.extensions);
+        merge_list(&mut self.exclude, other.exclude);
+        self.ignore_patterns.extend(other.ignore_patterns);
+        self.multiline_anchors |= other.multiline_anchors;
+        self.line_endings = other.line_endings.or(self.line_endings);
+    }
+}
+
+/// Extends an optional list with another, where `None` means the list was not given.
+fn merge_list(list: &mut Option<Vec<String>>, other: Option<Vec<String>>) {
+    if let Some(other) = other {
+        list.get_or_insert_with(Vec::new).extend(other);
+    }
 }
 
 /// Defines a block of text to be ignored, specified by start and end patterns.
@@ -48,6 +147,21 @@ pub struct Block {
     pub end: String,
 }
 
+/// The name of the ignore file that every command honors, in any walked directory.
+/// It uses gitignore syntax but applies outside git repositories too.
+pub const IGNORE_FILENAME: &str = ".ooberignore";
+
+/// Which paths a directory walk visits, shared by the commands that walk a tree.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WalkOptions {
+    /// Include hidden files and directories.
+    pub hidden: bool,
+    /// Walk the paths that ignore files (e.g. `.gitignore`, `.ooberignore`) exclude.
+    pub no_ignore: bool,
+    /// Descend into symlinked directories.
+    pub follow_symlinks: bool,
+}
+
 /// A utility for loading scanner and replacer configurations.
 pub struct ConfigLoader;
 
@@ -97,26 +211,26 @@ impl ConfigLoader {
         }
 
         // Try in the executable's directory
-        if let Ok(exe_path) = env::current_exe() {
-            if let Some(exe_dir) = exe_path.parent() {
-                let exe_config = exe_dir.join(config_path);
-                if exe_config.exists() {
-                    return Ok(exe_config);
-                }
+        if let Ok(exe_path) = env::current_exe()
+            && let Some(exe_dir) = exe_path.parent()
+        {
+            let exe_config = exe_dir.join(config_path);
+            if exe_config.exists() {
+                return Ok(exe_config);
+            }
 
-                // Also check parent directory (in case we're in target/release)
-                if let Some(parent) = exe_dir.parent() {
-                    let parent_config = parent.join(config_path);
-                    if parent_config.exists() {
-                        return Ok(parent_config);
-                    }
+            // Also check parent directory (in case we're in target/release)
+            if let Some(parent) = exe_dir.parent() {
+                let parent_config = parent.join(config_path);
+                if parent_config.exists() {
+                    return Ok(parent_config);
+                }
 
-                    // Check one more level up (for target/release structure)
-                    if let Some(grandparent) = parent.parent() {
-                        let grandparent_config = grandparent.join(config_path);
-                        if grandparent_config.exists() {
-                            return Ok(grandparent_config);
-                        }
+                // Check one more level up (for target/release structure)
+                if let Some(grandparent) = parent.parent() {
+                    let grandparent_config = grandparent.join(config_path);
+                    if grandparent_config.exists() {
+                        return Ok(grandparent_config);
                     }
                 }
             }
@@ -138,10 +252,10 @@ impl ConfigLoader {
             );
         }
 
-        if let Ok(exe_path) = env::current_exe() {
-            if let Some(exe_dir) = exe_path.parent() {
-                tried_locations.push(exe_dir.join(config_path).display().to_string());
-            }
+        if let Ok(exe_path) = env::current_exe()
+            && let Some(exe_dir) = exe_path.parent()
+        {
+            tried_locations.push(exe_dir.join(config_path).display().to_string());
         }
 
         Err(format!(
@@ -152,12 +266,71 @@ impl ConfigLoader {
         .into())
     }
 
+    /// Resolves a relative path against an optional base directory.
+    ///
+    /// This is used by `--root` so that relative inputs and config paths don't depend
+    /// on the process's current directory. Absolute paths, and all paths when no base
+    /// is given, are returned unchanged.
+    pub fn resolve_path(root: Option<&Path>, path: &Path) -> PathBuf {
+        match root {
+            Some(root) if path.is_relative() => root.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Builds a walker for `dir` that skips hidden files and the paths excluded by
+    /// `.gitignore`, `.ignore` and `.ooberignore` files, unless `options` say otherwise.
+    ///
+    /// `.ooberignore` rules take precedence over the other ignore files, so a `!` rule
+    /// in one can re-include a path that `.gitignore` excludes.
+    pub fn walk_builder(dir: &Path, options: WalkOptions) -> WalkBuilder {
+        let mut walker = WalkBuilder::new(dir);
+        walker
+            .standard_filters(!options.no_ignore)
+            .hidden(!options.hidden)
+            .follow_links(options.follow_symlinks);
+        if !options.no_ignore {
+            walker.add_custom_ignore_filename(IGNORE_FILENAME);
+        }
+        walker
+    }
+
+    /// Skips the paths matched by gitignore-syntax `ignore_patterns` during a walk.
+    ///
+    /// The rules are anchored at `root`, which should be the directory the walk starts
+    /// from, so `/build` only matches `<root>/build`. Ignored directories are not descended.
+    pub fn apply_ignore_patterns(
+        walker: &mut WalkBuilder,
+        root: &Path,
+        ignore_patterns: &[String],
+    ) -> Result<()> {
+        if ignore_patterns.is_empty() {
+            return Ok(());
+        }
+
+        let mut builder = GitignoreBuilder::new(root);
+        for rule in ignore_patterns {
+            builder.add_line(None, rule)?;
+        }
+        let gitignore = builder.build()?;
+
+        walker.filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            !gitignore.matched(entry.path(), is_dir).is_ignore()
+        });
+        Ok(())
+    }
+
     /// Loads a `ScanConfig` from a YAML file.
     ///
+    /// A file with several YAML documents (separated by `---`) is merged into one config,
+    /// with the patterns of each document in order.
+    ///
     /// # Arguments
     ///
     /// * `path` - The path to the YAML configuration file.
     pub fn load_scan_config(path: &Path) -> Result<ScanConfig> {
+        debug!("Loading scan config from {}", path.display());
         let file = File::open(path).map_err(|e| {
             Error::Config(format!(
                 "could not open patterns file '{}': {} (pass -e '<regex>' to scan without a file, or -p <file> to use a different one)",
@@ -165,16 +338,194 @@ impl ConfigLoader {
                 e
             ))
         })?;
-        Ok(serde_yaml::from_reader(file)?)
+        let mut documents = Self::load_documents::<ScanConfig>(path, file)?.into_iter();
+        let mut config = documents.next().expect("at least one document");
+        documents.for_each(|document| config.merge(document));
+        Ok(config)
+    }
+
+    /// Loads and merges every `.yaml` and `.yml` file directly inside `dir` (e.g. pattern
+    /// packs published separately), in file name order.
+    ///
+    /// When several files define a pattern of the same name, the first one is kept and
+    /// the others are skipped with a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory holding the YAML configuration files.
+    pub fn load_scan_config_dir(dir: &Path) -> Result<ScanConfig> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map_err(|e| {
+                Error::Config(format!("could not read patterns directory '{}': {e}", dir.display()))
+            })?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml")
+            })
+            .collect();
+        files.sort();
+        if files.is_empty() {
+            return Err(Error::Config(format!(
+                "no .yaml pattern files found in '{}'",
+                dir.display()
+            )));
+        }
+
+        let mut config = ScanConfig {
+            patterns: Vec::new(),
+            ignore_patterns: Vec::new(),
+            multiline_anchors: false,
+        };
+        // The file each pattern name was first defined in
+        let mut defined_in: HashMap<String, PathBuf> = HashMap::new();
+        for file in files {
+            let mut pack = Self::load_scan_config(&file)?;
+            pack.patterns.retain(|pattern| match defined_in.get(&pattern.name) {
+                Some(first) => {
+                    warn!(
+                        "Pattern '{}' in {} is already defined in {}; skipping it",
+                        pattern.name,
+                        file.display(),
+                        first.display()
+                    );
+                    false
+                }
+                None => {
+                    defined_in.insert(pattern.name.clone(), file.clone());
+                    true
+                }
+            });
+            config.merge(pack);
+        }
+        Ok(config)
     }
 
     /// Loads a `ReplaceConfig` from a YAML file.
     ///
+    /// Like `load_scan_config`, multiple YAML documents are merged into one config.
+    ///
     /// # Arguments
     ///
     /// * `path` - The path to the YAML configuration file.
     pub fn load_replace_config(path: &Path) -> Result<ReplaceConfig> {
+        debug!("Loading replace config from {}", path.display());
         let file = File::open(path)?;
-        Ok(serde_yaml::from_reader(file)?)
+        let mut documents = Self::load_documents::<ReplaceConfig>(path, file)?.into_iter();
+        let mut config = documents.next().expect("at least one document");
+        documents.for_each(|document| config.merge(document));
+        Ok(config)
+    }
+
+    /// Deserializes every YAML document in `file`, skipping empty ones (such as the one
+    /// left by a trailing `---`). Fails if there is no non-empty document.
+    fn load_documents<T: DeserializeOwned>(path: &Path, file: File) -> Result<Vec<T>> {
+        let mut documents = Vec::new();
+        for document in serde_yaml::Deserializer::from_reader(file) {
+            documents.extend(Option::<T>::deserialize(document)?);
+        }
+        if documents.is_empty() {
+            return Err(Error::Config(format!("config file '{}' is empty", path.display())));
+        }
+        Ok(documents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_multi_document_configs_are_merged() {
+        let temp_dir = TempDir::new().unwrap();
+        let scan_path = temp_dir.path().join("patterns.yaml");
+        fs::write(
+            &scan_path,
+            "patterns:\n  - name: todo\n    pattern: TODO\nignore_patterns: [vendor/]\n\
+             ---\npatterns:\n  - name: fixme\n    pattern: FIXME\n---\n",
+        )
+        .unwrap();
+
+        let config = ConfigLoader::load_scan_config(&scan_path).unwrap();
+        let names: Vec<&str> = config.patterns.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["todo", "fixme"]);
+        assert_eq!(config.ignore_patterns, ["vendor/"]);
+
+        let replace_path = temp_dir.path().join("replace.yaml");
+        fs::write(
+            &replace_path,
+            "patterns: [a, b]\nreplacements: [A]\n---\n\
+             patterns: [c]\nreplacements: [C]\nextensions: [js]\n",
+        )
+        .unwrap();
+
+        let config = ConfigLoader::load_replace_config(&replace_path).unwrap();
+        assert_eq!(config.patterns, ["a", "b", "c"]);
+        assert_eq!(config.replacements, [Some("A".to_string()), None, Some("C".to_string())]);
+        assert_eq!(config.extensions, Some(vec!["js".to_string()]));
+
+        fs::write(&scan_path, "").unwrap();
+        assert!(ConfigLoader::load_scan_config(&scan_path).is_err());
+    }
+
+    #[test]
+    fn test_patterns_dir_merges_every_pack() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(
+            dir.join("b-secrets.yml"),
+            "patterns:\n  - name: aws_key\n    pattern: AKIA\n  - name: todo\n    pattern: XXX\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("a-notes.yaml"),
+            "patterns:\n  - name: todo\n    pattern: TODO\nignore_patterns: [vendor/]\n",
+        )
+        .unwrap();
+        fs::write(dir.join("README.md"), "not a pack").unwrap();
+
+        let config = ConfigLoader::load_scan_config_dir(dir).unwrap();
+        let patterns: Vec<(&str, &str)> =
+            config.patterns.iter().map(|p| (p.name.as_str(), p.pattern.as_str())).collect();
+        // The second `todo` (from a later file) is skipped
+        assert_eq!(patterns, [("todo", "TODO"), ("aws_key", "AKIA")]);
+        assert_eq!(config.ignore_patterns, ["vendor/"]);
+
+        assert!(ConfigLoader::load_scan_config_dir(&dir.join("missing")).is_err());
+        let empty = TempDir::new().unwrap();
+        assert!(ConfigLoader::load_scan_config_dir(empty.path()).is_err());
+    }
+
+    #[test]
+    fn test_ooberignore_takes_precedence_over_gitignore() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir(root.join("vendor")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join(IGNORE_FILENAME), "vendor/\n!keep.log\n").unwrap();
+        for name in ["main.rs", "vendor/lib.rs", "debug.log", "keep.log"] {
+            fs::write(root.join(name), "x").unwrap();
+        }
+
+        let walk = |options: WalkOptions| {
+            let mut files: Vec<String> = ConfigLoader::walk_builder(root, options)
+                .build()
+                .map(|entry| entry.unwrap())
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| {
+                    let path = entry.path().strip_prefix(root).unwrap();
+                    path.to_string_lossy().replace('\\', "/")
+                })
+                .collect();
+            files.sort();
+            files
+        };
+
+        assert_eq!(walk(WalkOptions::default()), ["keep.log", "main.rs"]);
+        let no_ignore = WalkOptions { no_ignore: true, ..Default::default() };
+        assert_eq!(walk(no_ignore), ["debug.log", "keep.log", "main.rs", "vendor/lib.rs"]);
     }
 }
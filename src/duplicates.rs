@@ -0,0 +1,183 @@
+//! Implements the `duplicates` command, which finds copy-pasted lines across files.
+//!
+//! Every file is read line by line (binary files are skipped, as in `scan`) and each
+//! line is normalized by trimming it and collapsing runs of whitespace. With `--block`,
+//! runs of consecutive non-blank lines are compared instead. Lines or blocks that occur
+//! at least `--min-count` times are reported with all their locations.
+
+use crate::cli::DuplicatesArgs;
+use crate::errors::Result;
+use crate::scanner::{collect_files, open_text_file, resolve_workers, FileFilter, TextEncoding};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+/// A line or block that occurs more than once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    /// The normalized text, with the lines of a block joined by `\n`.
+    pub text: String,
+    /// Every occurrence, in file and line order.
+    pub locations: Vec<Location>,
+}
+
+/// Where a duplicated line or block starts and ends (1-based, inclusive).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// The main entry point for the `duplicates` command.
+pub fn run_duplicates(args: DuplicatesArgs) -> Result<()> {
+    let duplicates = find_duplicates(&args)?;
+
+    for duplicate in &duplicates {
+        println!("{}x  {}", duplicate.locations.len(), duplicate.text.replace('\n', "\n    "));
+        for location in &duplicate.locations {
+            let path = location.path.strip_prefix(&args.dir).unwrap_or(&location.path);
+            if location.start_line == location.end_line {
+                println!("      {}:{}", path.display(), location.start_line);
+            } else {
+                println!(
+                    "      {}:{}-{}",
+                    path.display(),
+                    location.start_line,
+                    location.end_line
+                );
+            }
+        }
+    }
+    let kind = if args.block > 1 { "blocks" } else { "lines" };
+    println!("\nDuplicated {kind}: {}", duplicates.len());
+
+    Ok(())
+}
+
+/// Finds the lines (or blocks) under `args.dir` that occur at least `args.min_count`
+/// times. The most frequent come first, then by first location.
+pub fn find_duplicates(args: &DuplicatesArgs) -> Result<Vec<Duplicate>> {
+    if args.block == 0 {
+        return Err("--block must be at least 1".into());
+    }
+
+    let filter = FileFilter::from_extensions(&args.extensions);
+    let files = collect_files(std::slice::from_ref(&args.dir), &filter, false)?;
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(resolve_workers(args.workers))
+        .build()?;
+    let per_file: Vec<Vec<(String, Location)>> = pool.install(|| {
+        files
+            .par_iter()
+            .map(|path| {
+                file_entries(path, args.block, args.min_length).map_err(|e| e.for_file(path))
+            })
+            .collect::<Result<_>>()
+    })?;
+
+    let mut occurrences: HashMap<String, Vec<Location>> = HashMap::new();
+    for (text, location) in per_file.into_iter().flatten() {
+        occurrences.entry(text).or_default().push(location);
+    }
+
+    let mut duplicates: Vec<Duplicate> = occurrences
+        .into_iter()
+        .filter(|(_, locations)| locations.len() >= args.min_count.max(2))
+        .map(|(text, mut locations)| {
+            locations.sort();
+            Duplicate { text, locations }
+        })
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.locations
+            .len()
+            .cmp(&a.locations.len())
+            .then_with(|| a.locations[0].cmp(&b.locations[0]))
+    });
+
+    Ok(duplicates)
+}
+
+/// Returns the normalized lines (or blocks of `block` non-blank lines) of `path` that
+/// are at least `min_length` characters long, with their locations.
+fn file_entries(path: &Path, block: usize, min_length: usize) -> Result<Vec<(String, Location)>> {
+    let Some(reader) = open_text_file(path, TextEncoding::Auto)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut lines = Vec::new();
+    for (i, line) in reader.split(b'\n').enumerate() {
+        let line = line?;
+        let normalized = normalize(&String::from_utf8_lossy(&line));
+        if !normalized.is_empty() {
+            lines.push((i + 1, normalized));
+        }
+    }
+
+    Ok(lines
+        .windows(block)
+        .filter_map(|window| {
+            let text = window.iter().map(|(_, line)| line.as_str()).collect::<Vec<_>>().join("\n");
+            (text.chars().count() >= min_length).then(|| {
+                let location = Location {
+                    path: path.to_path_buf(),
+                    start_line: window[0].0,
+                    end_line: window[block - 1].0,
+                };
+                (text, location)
+            })
+        })
+        .collect())
+}
+
+/// Trims `line` and collapses each run of whitespace inside it to a single space.
+fn normalize(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Args, Commands};
+    use clap::Parser;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn duplicates_args(argv: &[&str]) -> DuplicatesArgs {
+        let argv = ["ob", "duplicates"].iter().chain(argv);
+        match Args::try_parse_from(argv).unwrap().command {
+            Commands::Duplicates(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_line_duplicated_across_files_is_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.js");
+        let b = temp_dir.path().join("b.js");
+        fs::write(&a, "function a() {\n  return   compute(total, rate);\n}\n").unwrap();
+        fs::write(&b, "// b\nfunction b() {\n\treturn compute(total, rate);\n}\n").unwrap();
+
+        let dir = temp_dir.path().to_str().unwrap();
+        let duplicates = find_duplicates(&duplicates_args(&["-d", dir])).unwrap();
+
+        assert_eq!(duplicates.len(), 1, "{duplicates:?}");
+        assert_eq!(duplicates[0].text, "return compute(total, rate);");
+        let locations: Vec<(&Path, usize)> = duplicates[0]
+            .locations
+            .iter()
+            .map(|l| (l.path.as_path(), l.start_line))
+            .collect();
+        assert_eq!(locations, [(a.as_path(), 2), (b.as_path(), 3)]);
+
+        // As a 2-line block, the differing function names no longer line up
+        let blocks = find_duplicates(&duplicates_args(&["-d", dir, "--block", "2"])).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].text, "return compute(total, rate);\n}");
+        assert_eq!(blocks[0].locations[1].end_line, 4);
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// The primary error type for all operations in the `oober` application.
@@ -57,6 +57,25 @@ pub enum Error {
     /// An error from the `walkdir` crate.
     #[error("Walkdir error: {0}")]
     WalkDir(#[from] walkdir::Error),
+
+    /// An error while fetching a URL input (`remote` feature).
+    #[cfg(feature = "remote")]
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+impl Error {
+    /// Attaches `path` to this error as `Error::Processing`, so the message names the
+    /// file that failed. Errors that already carry a path are returned unchanged.
+    pub fn for_file(self, path: &Path) -> Self {
+        match self {
+            Error::Processing { .. } => self,
+            source => Error::Processing {
+                path: path.to_path_buf(),
+                source: Box::new(source),
+            },
+        }
+    }
 }
 
 /// A convenient type alias for `Result<T, oober::errors::Error>`.
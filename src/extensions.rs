@@ -0,0 +1,109 @@
+//! Implements the `extensions` command, which lists the file types present in a tree.
+//!
+//! The tree is walked like `scan` walks it (ignore rules apply, hidden files are
+//! skipped) and the files are grouped by lowercased extension, so the names shown can
+//! be passed straight to `-x`. Files without an extension are grouped as `(none)`.
+
+use crate::cli::ExtensionsArgs;
+use crate::errors::{Error, Result};
+use crate::scanner::{collect_files, FileFilter};
+use std::collections::HashMap;
+
+/// How many files share an extension, and their combined size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionStats {
+    /// The lowercased extension without its dot, or `(none)`.
+    pub extension: String,
+    pub files: usize,
+    /// The total size of the files, in bytes.
+    pub bytes: u64,
+}
+
+/// The main entry point for the `extensions` command.
+pub fn run_extensions(args: ExtensionsArgs) -> Result<()> {
+    let extensions = list_extensions(&args)?;
+
+    for stats in &extensions {
+        println!(
+            "{:<12} {:>8} files  {:>10.2} MB",
+            stats.extension,
+            stats.files,
+            stats.bytes as f64 / 1_048_576.0
+        );
+    }
+    let files: usize = extensions.iter().map(|stats| stats.files).sum();
+    println!("\nExtensions: {}  Files: {}", extensions.len(), files);
+
+    Ok(())
+}
+
+/// Counts the files under `args.dir` by extension. The most common extensions come
+/// first, then by name.
+pub fn list_extensions(args: &ExtensionsArgs) -> Result<Vec<ExtensionStats>> {
+    let files = collect_files(std::slice::from_ref(&args.dir), &FileFilter::default(), false)?;
+
+    let mut by_extension: HashMap<String, ExtensionStats> = HashMap::new();
+    for path in &files {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "(none)".to_string());
+        let size = std::fs::metadata(path).map_err(|e| Error::from(e).for_file(path))?.len();
+        let stats = by_extension.entry(extension.clone()).or_insert(ExtensionStats {
+            extension,
+            files: 0,
+            bytes: 0,
+        });
+        stats.files += 1;
+        stats.bytes += size;
+    }
+
+    let mut extensions: Vec<ExtensionStats> = by_extension.into_values().collect();
+    extensions.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.extension.cmp(&b.extension)));
+    Ok(extensions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Args, Commands};
+    use clap::Parser;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extensions_are_counted_by_frequency() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("src/nested/lib.RS"), "pub mod a;\n").unwrap();
+        fs::write(root.join("src/nested/util.rs"), "").unwrap();
+        fs::write(root.join("README.md"), "# Title\n").unwrap();
+        fs::write(root.join("Makefile"), "all:\n").unwrap();
+        fs::write(root.join("notes.md"), "x").unwrap();
+        // Ignored and hidden files are skipped, as in `scan`
+        fs::write(root.join(".ignore"), "build/\n").unwrap();
+        fs::create_dir(root.join("build")).unwrap();
+        fs::write(root.join("build/out.js"), "x").unwrap();
+
+        let argv = ["ob", "extensions", "-d", root.to_str().unwrap()];
+        let Commands::Extensions(args) = Args::try_parse_from(argv).unwrap().command else {
+            unreachable!()
+        };
+        let found: Vec<(String, usize, u64)> = list_extensions(&args)
+            .unwrap()
+            .into_iter()
+            .map(|stats| (stats.extension, stats.files, stats.bytes))
+            .collect();
+
+        assert_eq!(
+            found,
+            [
+                ("rs".to_string(), 3, 24),
+                ("md".to_string(), 2, 9),
+                ("(none)".to_string(), 1, 5),
+            ]
+        );
+    }
+}
@@ -1,52 +1,94 @@
+use crate::cli::RenameArgs;
+use crate::config::{ConfigLoader, WalkOptions};
 use crate::errors::Result;
-use ignore::WalkBuilder;
+use crate::scanner::skip_symlink_loop;
+use log::{debug, error};
 use rayon::prelude::*;
 use regex::Regex;
+use std::borrow::Cow;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// How many times a rename that fails with a transient error is attempted.
+const RENAME_ATTEMPTS: u32 = 3;
 
 /// Executes the file renaming process in a given directory.
 ///
 /// This function walks the specified directory, identifies files matching the
-/// provided regex pattern, and renames them using the replacement string. The
-/// operation is parallelized using Rayon for performance.
-///
-/// # Arguments
+/// provided regex pattern, and renames them using the replacement string and/or
+/// the prefix and suffix options. The operation is parallelized using Rayon for
+/// performance.
 ///
-/// * `dir` - The directory to process.
-/// * `pattern` - The regex pattern to match against filenames.
-/// * `replacement` - The replacement string. Can include capture groups like `$1`.
-/// * `dry_run` - If `true`, a preview of changes is shown without actually renaming files.
-/// * `workers` - The number of parallel worker threads. If `None`, it defaults to the
-///   number of logical CPU cores.
-pub fn run_rename(
-    dir: PathBuf,
-    pattern: String,
-    replacement: String,
-    dry_run: bool,
-    verbose: bool,
-    workers: Option<usize>,
-) -> Result<()> {
-    let regex = Regex::new(&pattern)?;
-    let replacer = Arc::new(FileRenamer::new(regex, replacement));
+/// With `include_dirs`, directories below `dir` are renamed too. They are renamed
+/// sequentially after all files, deepest first.
+pub fn run_rename(args: RenameArgs) -> Result<()> {
+    let RenameArgs {
+        dir,
+        pattern,
+        replacement,
+        add_prefix,
+        add_suffix,
+        strip_prefix,
+        strip_suffix,
+        dry_run,
+        verbose,
+        workers,
+        include_dirs,
+        hidden,
+        no_ignore,
+        follow_symlinks,
+    } = args;
+    let regex = pattern.as_deref().map(Regex::new).transpose()?;
+    let replacer = Arc::new(FileRenamer {
+        regex,
+        replacement,
+        affixes: Affixes {
+            add_prefix,
+            add_suffix,
+            strip_prefix,
+            strip_suffix,
+        },
+    });
 
     let mut all_files = Vec::new();
-    let mut walker = WalkBuilder::new(&dir);
-    walker.standard_filters(true);
+    let mut all_dirs = Vec::new();
+    let mut walker =
+        ConfigLoader::walk_builder(&dir, WalkOptions { hidden, no_ignore, follow_symlinks });
+    // Renaming inside a repository's `.git` would corrupt it, even with --hidden
+    walker.filter_entry(|entry| entry.file_name() != ".git");
 
-    for entry in walker.build() {
+    for entry in walker.build().filter_map(skip_symlink_loop) {
         let entry = entry?;
         let path = entry.path();
         if path.is_file() {
             all_files.push(path.to_path_buf());
+        } else if include_dirs && entry.depth() > 0 && path.is_dir() {
+            all_dirs.push(path.to_path_buf());
         }
     }
 
     let processed = AtomicUsize::new(0);
     let renamed = AtomicUsize::new(0);
-    let log_changes = verbose || dry_run;
+    let log_changes = verbose > 0 || dry_run;
+    let report = |path: &Path, result: Result<Option<PathBuf>>| {
+        match result {
+            Ok(Some(new_path)) => {
+                renamed.fetch_add(1, Ordering::Relaxed);
+                if log_changes {
+                    println!("Renamed: {} -> {}", path.display(), new_path.display());
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Error renaming {}: {}", path.display(), e);
+            }
+        }
+        processed.fetch_add(1, Ordering::Relaxed);
+    };
 
     let pool = rayon::ThreadPoolBuilder::new()
         .num_threads(workers.unwrap_or_else(|| {
@@ -57,23 +99,19 @@ pub fn run_rename(
         .build()?;
 
     pool.install(|| {
-        all_files.par_iter().for_each(|path| {
-            match replacer.rename_file(path, dry_run) {
-                Ok(Some(new_path)) => {
-                    renamed.fetch_add(1, Ordering::Relaxed);
-                    if log_changes {
-                        println!("Renamed: {} -> {}", path.display(), new_path.display());
-                    }
-                }
-                Ok(None) => {}
-                Err(e) => {
-                    eprintln!("Error renaming file {}: {}", path.display(), e);
-                }
-            }
-            processed.fetch_add(1, Ordering::Relaxed);
-        });
+        all_files
+            .par_iter()
+            .for_each(|path| report(path, replacer.rename_file(path, dry_run)));
     });
 
+    // Files are already in place, so only directory renames remain. Going deepest
+    // first means a directory is always renamed before any of its ancestors, and the
+    // paths collected during the walk stay valid.
+    all_dirs.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+    for path in &all_dirs {
+        report(path, replacer.rename_file(path, dry_run));
+    }
+
     println!("\n{}", "-".repeat(50));
     println!("Files scanned: {}", processed.load(Ordering::Relaxed));
     println!("Files renamed: {}", renamed.load(Ordering::Relaxed));
@@ -83,30 +121,55 @@ pub fn run_rename(
 
 /// A helper struct for renaming files based on a regex pattern.
 struct FileRenamer {
-    regex: Regex,
-    replacement: String,
+    /// Selects the files to rename. If `None`, every file is a candidate.
+    regex: Option<Regex>,
+    /// Rewrites the part of the name matched by `regex`.
+    replacement: Option<String>,
+    affixes: Affixes,
+}
+
+/// Fixed-text edits applied to a file name after the regex replacement. Prefixes and
+/// suffixes are stripped before new ones are added.
+struct Affixes {
+    add_prefix: Option<String>,
+    add_suffix: Option<String>,
+    strip_prefix: Option<String>,
+    strip_suffix: Option<String>,
 }
 
 impl FileRenamer {
-    /// Creates a new `FileRenamer`.
-    fn new(regex: Regex, replacement: String) -> Self {
-        Self { regex, replacement }
-    }
+    /// Computes the new path for a file based on the renaming rule, or `None` if the
+    /// rule leaves its name unchanged (or the name is not valid UTF-8).
+    fn get_new_path(&self, path: &Path) -> Option<PathBuf> {
+        let file_name = path.file_name()?.to_str()?;
+        let mut name = match &self.regex {
+            Some(regex) if !regex.is_match(file_name) => return None,
+            Some(regex) => match &self.replacement {
+                Some(replacement) => regex.replace_all(file_name, replacement.as_str()),
+                None => Cow::Borrowed(file_name),
+            },
+            None => Cow::Borrowed(file_name),
+        }
+        .into_owned();
 
-    /// Computes the new path for a file based on the renaming rule.
-    ///
-    /// # Optimization Note
-    ///
-    /// This function currently uses `unwrap()` which can panic if a filename is
-    /// not valid UTF-8. A more robust implementation would handle this case
-    /// gracefully, for example by skipping the file and logging a warning.
-    fn get_new_path(&self, path: &Path) -> PathBuf {
-        let file_name = path.file_name().unwrap().to_str().unwrap();
-        let new_file_name = self.regex.replace_all(file_name, self.replacement.as_str());
-        path.with_file_name(new_file_name.into_owned())
+        let Affixes { add_prefix, add_suffix, strip_prefix, strip_suffix } = &self.affixes;
+        if let Some(stripped) = strip_prefix.as_deref().and_then(|p| name.strip_prefix(p)) {
+            name = stripped.to_string();
+        }
+        if let Some(stripped) = strip_suffix.as_deref().and_then(|s| name.strip_suffix(s)) {
+            name = stripped.to_string();
+        }
+        if let Some(prefix) = add_prefix {
+            name.insert_str(0, prefix);
+        }
+        if let Some(suffix) = add_suffix {
+            name.push_str(suffix);
+        }
+
+        (!name.is_empty() && name != file_name).then(|| path.with_file_name(name))
     }
 
-    /// Renames a single file if its name matches the pattern.
+    /// Renames a single file or directory if its name matches the pattern.
     ///
     /// If `dry_run` is `true`, it checks if the file would be renamed but doesn't
     /// perform the operation.
@@ -114,16 +177,232 @@ impl FileRenamer {
     /// # Returns
     ///
     /// Returns `Ok(Some(new_path))` if the file was (or would be) renamed, and `Ok(None)` otherwise.
+    /// Renaming onto an existing path is an error rather than an overwrite.
     fn rename_file(&self, path: &Path, dry_run: bool) -> Result<Option<PathBuf>> {
-        let file_name = path.file_name().unwrap().to_str().unwrap();
-        if self.regex.is_match(file_name) {
-            let new_path = self.get_new_path(path);
-            if !dry_run {
-                fs::rename(path, &new_path)?;
+        debug!("Checking {}", path.display());
+        let Some(new_path) = self.get_new_path(path) else {
+            return Ok(None);
+        };
+        if new_path.symlink_metadata().is_ok() {
+            return Err(format!("{} already exists", new_path.display()).into());
+        }
+        if !dry_run {
+            move_path(path, &new_path, |from, to| fs::rename(from, to))?;
+        }
+        Ok(Some(new_path))
+    }
+}
+
+/// Moves `from` to `to` with `rename`, retrying transient failures (e.g. a file that is
+/// briefly locked) a few times. When the paths are on different filesystems, which
+/// `rename` cannot cross, the file or directory is copied over and then removed.
+fn move_path(
+    from: &Path,
+    to: &Path,
+    rename: impl Fn(&Path, &Path) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match rename(from, to) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                debug!("{} is on another device, copying it instead", to.display());
+                return copy_then_remove(from, to);
             }
-            Ok(Some(new_path))
-        } else {
-            Ok(None)
+            Err(e) if is_transient(&e) && attempt < RENAME_ATTEMPTS => {
+                debug!("Retrying rename of {} after: {e}", from.display());
+                std::thread::sleep(Duration::from_millis(20 * u64::from(attempt)));
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Whether a failed rename may succeed if it is simply tried again.
+fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+    )
+}
+
+/// Copies `from` to `to`, recursively for a directory, then removes `from`. A file that
+/// cannot be removed has its copy removed again, so it is not left in both places; a
+/// directory may already be partly removed, so its full copy is kept.
+fn copy_then_remove(from: &Path, to: &Path) -> io::Result<()> {
+    let is_dir = from.symlink_metadata()?.is_dir();
+    let remove = |path: &Path| {
+        if is_dir { fs::remove_dir_all(path) } else { fs::remove_file(path) }
+    };
+    if let Err(e) = copy_recursively(from, to) {
+        // Best effort: a partial copy is worse than none
+        let _ = remove(to);
+        return Err(e);
+    }
+    remove(from).inspect_err(|_| {
+        if !is_dir {
+            let _ = fs::remove_file(to);
+        }
+    })
+}
+
+/// Copies a file, symlink or directory tree from `from` to `to`.
+fn copy_recursively(from: &Path, to: &Path) -> io::Result<()> {
+    let file_type = from.symlink_metadata()?.file_type();
+    if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else if file_type.is_symlink() {
+        let target = fs::read_link(from)?;
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(target, to);
+        #[cfg(not(unix))]
+        return Err(io::Error::other(format!("cannot copy symlink to {}", target.display())));
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Args, Commands};
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn rename_args(dir: &Path, argv: &[&str]) -> RenameArgs {
+        let dir = dir.to_str().unwrap();
+        let argv = ["ob", "rename", "-d", dir].into_iter().chain(argv.iter().copied());
+        match Args::try_parse_from(argv).unwrap().command {
+            Commands::Rename(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_include_dirs_renames_nested_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("old_module").join("old_module_helpers");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(temp_dir.path().join("old_module").join("lib.rs"), "mod a;").unwrap();
+        fs::write(nested.join("old_module_util.rs"), "fn f() {}").unwrap();
+
+        let args = rename_args(
+            temp_dir.path(),
+            &["-p", "old_module", "-r", "new_module", "-w", "2", "--include-dirs"],
+        );
+        run_rename(args).unwrap();
+
+        let new_root = temp_dir.path().join("new_module");
+        assert!(!temp_dir.path().join("old_module").exists());
+        assert_eq!(fs::read_to_string(new_root.join("lib.rs")).unwrap(), "mod a;");
+        assert!(new_root.join("new_module_helpers").join("new_module_util.rs").is_file());
+    }
+
+    #[test]
+    fn test_prefix_and_suffix_options() {
+        let renamer = |argv: &[&str]| {
+            let args = rename_args(Path::new("."), argv);
+            FileRenamer {
+                regex: args.pattern.as_deref().map(|p| Regex::new(p).unwrap()),
+                replacement: args.replacement,
+                affixes: Affixes {
+                    add_prefix: args.add_prefix,
+                    add_suffix: args.add_suffix,
+                    strip_prefix: args.strip_prefix,
+                    strip_suffix: args.strip_suffix,
+                },
+            }
+        };
+        let new_name = |renamer: &FileRenamer, name: &str| {
+            renamer
+                .get_new_path(Path::new("dir").join(name).as_path())
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        };
+
+        let add_prefix = renamer(&["--add-prefix", "old_"]);
+        assert_eq!(new_name(&add_prefix, "main.rs").as_deref(), Some("old_main.rs"));
+        let add_suffix = renamer(&["--add-suffix", ".bak"]);
+        assert_eq!(new_name(&add_suffix, "main.rs").as_deref(), Some("main.rs.bak"));
+        let strip_prefix = renamer(&["--strip-prefix", "old_"]);
+        assert_eq!(new_name(&strip_prefix, "old_main.rs").as_deref(), Some("main.rs"));
+        assert_eq!(new_name(&strip_prefix, "main.rs"), None);
+        let strip_suffix = renamer(&["--strip-suffix", ".tmp"]);
+        assert_eq!(new_name(&strip_suffix, "report.csv.tmp").as_deref(), Some("report.csv"));
+        assert_eq!(new_name(&strip_suffix, ".tmp"), None);
+
+        // Options compose, and -p without -r only selects the files they apply to
+        let combined = renamer(&["-p", r"\.tmp$", "--strip-suffix", ".tmp", "--add-prefix", "v2_"]);
+        assert_eq!(new_name(&combined, "data.json.tmp").as_deref(), Some("v2_data.json"));
+        assert_eq!(new_name(&combined, "data.json"), None);
+
+        let no_operation = ["ob", "rename", "-d", ".", "-p", "x"];
+        assert!(Args::try_parse_from(no_operation).is_err());
+    }
+
+    #[test]
+    fn test_rename_respects_dry_run_and_collisions() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.txt.tmp"), "new").unwrap();
+        fs::write(temp_dir.path().join("b.txt.tmp"), "new").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "keep").unwrap();
+
+        run_rename(rename_args(temp_dir.path(), &["--strip-suffix", ".tmp", "--dry-run"])).unwrap();
+        assert_eq!(file_names(temp_dir.path()), ["a.txt.tmp", "b.txt", "b.txt.tmp"]);
+
+        run_rename(rename_args(temp_dir.path(), &["--strip-suffix", ".tmp"])).unwrap();
+        assert_eq!(file_names(temp_dir.path()), ["a.txt", "b.txt", "b.txt.tmp"]);
+        assert_eq!(fs::read_to_string(temp_dir.path().join("b.txt")).unwrap(), "keep");
+    }
+
+    #[test]
+    fn test_moves_across_devices_fall_back_to_copying() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("dir/nested")).unwrap();
+        fs::write(root.join("dir/nested/a.txt"), "a").unwrap();
+        fs::write(root.join("file.txt"), "b").unwrap();
+
+        // Simulates a mount point boundary, which rename cannot cross
+        let cross_device = |_: &Path, _: &Path| Err(io::ErrorKind::CrossesDevices.into());
+        move_path(&root.join("dir"), &root.join("moved"), cross_device).unwrap();
+        move_path(&root.join("file.txt"), &root.join("moved.txt"), cross_device).unwrap();
+        assert_eq!(file_names(root), ["moved", "moved.txt"]);
+        assert_eq!(fs::read_to_string(root.join("moved/nested/a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(root.join("moved.txt")).unwrap(), "b");
+
+        // Transient failures are retried, up to RENAME_ATTEMPTS times
+        let failures = &AtomicUsize::new(0);
+        let flaky = |failing: usize| {
+            failures.store(0, Ordering::Relaxed);
+            move |from: &Path, to: &Path| {
+                if failures.fetch_add(1, Ordering::Relaxed) < failing {
+                    return Err(io::ErrorKind::ResourceBusy.into());
+                }
+                fs::rename(from, to)
+            }
+        };
+        move_path(&root.join("moved.txt"), &root.join("retried.txt"), flaky(2)).unwrap();
+        assert!(root.join("retried.txt").exists());
+        let error = move_path(&root.join("retried.txt"), &root.join("x.txt"), flaky(3));
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::ResourceBusy);
+        assert!(root.join("retried.txt").exists());
+    }
+}
@@ -0,0 +1,199 @@
+//! A lightweight, line-oriented lexer that tells code apart from comments and strings.
+//!
+//! This is a heuristic used to drop scan matches that only appear in comments or
+//! string literals. It knows the comment and string delimiters of a handful of
+//! language families, chosen by file extension, and tracks block comments and
+//! multi-line strings across lines. It does not handle nested comments, raw
+//! strings, or heredocs.
+
+use std::path::Path;
+
+/// The kind of source text at a given position in a line.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeContext {
+    /// Anything outside a comment or string literal.
+    #[value(skip)]
+    Code,
+    /// A line or block comment, including its delimiters.
+    Comment,
+    /// A string literal, including its quotes.
+    String,
+}
+
+/// The comment and string delimiters of a language family.
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    /// String delimiters, longest first, and whether the string may span lines.
+    strings: &'static [(&'static str, bool)],
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[("\"", false), ("'", false), ("`", true)],
+};
+
+// `'` is left out because it also introduces lifetimes and labels.
+const RUST: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &[("\"", true)],
+};
+
+const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    strings: &[("\"\"\"", true), ("'''", true), ("\"", false), ("'", false)],
+};
+
+const HASH: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    strings: &[("\"", false), ("'", false)],
+};
+
+const SQL: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("/*", "*/")],
+    strings: &[("'", false), ("\"", false)],
+};
+
+impl Syntax {
+    fn for_path(path: &Path) -> Option<&'static Syntax> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "rs" => Some(&RUST),
+            "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "kt" | "scala" | "swift" | "go"
+            | "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "php" => Some(&C_LIKE),
+            "py" | "pyi" => Some(&PYTHON),
+            "sh" | "bash" | "zsh" | "rb" | "pl" | "yaml" | "yml" | "toml" | "r" => Some(&HASH),
+            "sql" => Some(&SQL),
+            _ => None,
+        }
+    }
+}
+
+/// A comment or string left open at the end of a line.
+#[derive(Debug, Clone, Copy)]
+struct Open {
+    context: CodeContext,
+    close: &'static str,
+    spans_lines: bool,
+}
+
+/// Classifies the lines of one file, in order.
+pub struct Lexer {
+    syntax: &'static Syntax,
+    open: Option<Open>,
+}
+
+/// The contexts of one line, as the byte offsets at which each context starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineContexts {
+    spans: Vec<(usize, CodeContext)>,
+}
+
+impl LineContexts {
+    /// Returns the context of the byte at `offset`.
+    pub fn at(&self, offset: usize) -> CodeContext {
+        let idx = self.spans.partition_point(|(start, _)| *start <= offset);
+        self.spans[idx.saturating_sub(1)].1
+    }
+}
+
+impl Lexer {
+    /// Returns a lexer for the language of `path`, or `None` if the extension is not
+    /// one the lexer knows.
+    pub fn for_path(path: &Path) -> Option<Self> {
+        Syntax::for_path(path).map(|syntax| Self { syntax, open: None })
+    }
+
+    /// Classifies `line`, which must be the next line of the file (without its
+    /// newline), carrying any unclosed block comment or multi-line string forward.
+    pub fn classify(&mut self, line: &str) -> LineContexts {
+        let bytes = line.as_bytes();
+        let mut open = self.open.take();
+        let mut spans = vec![(0, open.map_or(CodeContext::Code, |o| o.context))];
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            if let Some(o) = open {
+                if o.context == CodeContext::String && rest[0] == b'\\' {
+                    i += 2;
+                } else if rest.starts_with(o.close.as_bytes()) {
+                    i += o.close.len();
+                    open = None;
+                    spans.push((i, CodeContext::Code));
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+
+            if self.syntax.line_comments.iter().any(|c| rest.starts_with(c.as_bytes())) {
+                spans.push((i, CodeContext::Comment));
+                return LineContexts { spans };
+            }
+            let block_comment =
+                self.syntax.block_comments.iter().find(|(start, _)| rest.starts_with(start.as_bytes()));
+            if let Some((start, end)) = block_comment {
+                spans.push((i, CodeContext::Comment));
+                open = Some(Open { context: CodeContext::Comment, close: end, spans_lines: true });
+                i += start.len();
+            } else if let Some(&(quote, spans_lines)) =
+                self.syntax.strings.iter().find(|(quote, _)| rest.starts_with(quote.as_bytes()))
+            {
+                spans.push((i, CodeContext::String));
+                open = Some(Open { context: CodeContext::String, close: quote, spans_lines });
+                i += quote.len();
+            } else {
+                i += 1;
+            }
+        }
+
+        // Single-line strings never carry over, even if left unterminated.
+        self.open = open.filter(|o| o.spans_lines);
+        LineContexts { spans }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contexts(path: &str, lines: &[&str]) -> Vec<LineContexts> {
+        let mut lexer = Lexer::for_path(Path::new(path)).unwrap();
+        lines.iter().map(|line| lexer.classify(line)).collect()
+    }
+
+    #[test]
+    fn test_line_comments_and_strings() {
+        let line = r#"let s = "a \" // b"; // note"#;
+        let ctx = &contexts("main.js", &[line])[0];
+        assert_eq!(ctx.at(line.find("let").unwrap()), CodeContext::Code);
+        assert_eq!(ctx.at(line.find("// b").unwrap()), CodeContext::String);
+        assert_eq!(ctx.at(line.find(';').unwrap()), CodeContext::Code);
+        assert_eq!(ctx.at(line.find("note").unwrap()), CodeContext::Comment);
+    }
+
+    #[test]
+    fn test_block_comments_and_multiline_strings_span_lines() {
+        let c = contexts("lib.c", &["x = 1; /* start", "still comment", "end */ y = 2;"]);
+        assert_eq!(c[0].at(0), CodeContext::Code);
+        assert_eq!(c[1].at(0), CodeContext::Comment);
+        assert_eq!(c[2].at(0), CodeContext::Comment);
+        assert_eq!(c[2].at(8), CodeContext::Code);
+
+        let py = contexts("app.py", &["doc = \"\"\"", "key = 'abc'", "\"\"\"", "key = 'abc'"]);
+        assert_eq!(py[1].at(0), CodeContext::String);
+        assert_eq!(py[3].at(0), CodeContext::Code);
+        assert_eq!(py[3].at(7), CodeContext::String);
+
+        // An unterminated single-line string does not leak into the next line
+        let js = contexts("a.js", &["s = 'oops", "code()"]);
+        assert_eq!(js[1].at(0), CodeContext::Code);
+        assert!(Lexer::for_path(Path::new("notes.txt")).is_none());
+    }
+}
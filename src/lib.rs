@@ -14,13 +14,21 @@
 //! The library is designed to be fast, using parallel processing with Rayon and
 //! efficient directory traversal with the `ignore` crate.
 
+pub mod blame;
 pub mod cli;
 pub mod config;
+pub mod duplicates;
 pub mod errors;
+pub mod explain;
+pub mod extensions;
 pub mod file_renamer;
 pub mod fingerprint;
+pub mod lexer;
+pub mod logging;
 pub mod output_formatter;
 pub mod patterns;
+#[cfg(feature = "remote")]
+pub mod remote;
 pub mod replacer;
 pub mod scanner;
 pub mod state_manager;
@@ -0,0 +1,81 @@
+//! Diagnostic logging for the `oober` command-line tool.
+//!
+//! Command results (matches, summaries, `Modified ...` lines) are written to stdout
+//! by each command. Everything else — warnings, per-file errors, and debug tracing —
+//! goes through the `log` facade to stderr, so it never mixes with output that is
+//! piped into another tool.
+
+use log::LevelFilter;
+
+/// Maps the number of `-v` flags to a log level.
+///
+/// Without `-v` only warnings and errors are shown. `-v` adds informational
+/// messages, `-vv` adds per-file debug lines, and `-vvv` enables everything.
+pub fn level_for(verbosity: u8) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Initializes the global logger for the given verbosity.
+///
+/// The `RUST_LOG` environment variable can still override the level, e.g. to
+/// enable debug output for a single module.
+pub fn init(verbosity: u8) {
+    env_logger::Builder::new()
+        .filter_level(level_for(verbosity))
+        .parse_default_env()
+        .format_timestamp(None)
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Pattern;
+    use crate::scanner::Scanner;
+    use log::{Log, Metadata, Record};
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    struct CaptureLogger(Mutex<Vec<String>>);
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= log::max_level()
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                let line = format!("{} {}", record.level(), record.args());
+                self.0.lock().unwrap().push(line);
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger(Mutex::new(Vec::new()));
+
+    #[test]
+    fn test_per_file_debug_line_at_higher_verbosity() {
+        log::set_logger(&LOGGER).unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("verbose.txt");
+        std::fs::write(&path, "nothing to see\n").unwrap();
+        let scanner = Scanner::new(vec![Pattern::new("todo", "TODO")]).unwrap();
+        let debug_line = format!("DEBUG Scanning {}", path.display());
+
+        log::set_max_level(level_for(1));
+        scanner.scan_file(&path).unwrap();
+        assert!(!LOGGER.0.lock().unwrap().contains(&debug_line));
+
+        log::set_max_level(level_for(2));
+        scanner.scan_file(&path).unwrap();
+        assert!(LOGGER.0.lock().unwrap().contains(&debug_line));
+    }
+}
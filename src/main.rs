@@ -5,8 +5,11 @@
 
 use oober::cli::{self, Commands};
 use oober::errors::Result;
-use oober::{replacer, scanner, file_renamer};
+use oober::{
+    duplicates, explain, extensions, file_renamer, logging, output_formatter, replacer, scanner,
+};
 use std::env;
+use std::io::{self, IsTerminal};
 use std::process;
 
 /// The main function of the application.
@@ -31,7 +34,8 @@ fn main() -> Result<()> {
     // Check for specific commands with missing args and show examples
     if args_vec.len() == 2 {
         match args_vec[1].as_str() {
-            "scan" => {
+            // With data piped in, a bare `ob scan` scans standard input
+            "scan" if io::stdin().is_terminal() => {
                 eprintln!("Error: Missing required argument: <INPUTS>\n");
                 eprintln!("USAGE EXAMPLES:");
                 eprintln!("  ob scan .                              # Scan current directory");
@@ -82,69 +86,23 @@ fn main() -> Result<()> {
     }
 
     let args = cli::parse_args();
+    logging::init(args.command.verbosity());
 
     match args.command {
-        Commands::Scan {
-            patterns,
-            regex,
-            output,
-            extensions,
-            workers,
-            incremental,
-            force_full,
-            clear_cache,
-            content_hash,
-            format,
-            include_summary,
-            inputs,
-        } => scanner::run_scan(
-            patterns,
-            regex,
-            output,
-            extensions,
-            inputs,
-            workers,
-            incremental,
-            force_full,
-            clear_cache,
-            content_hash,
-            format,
-            include_summary,
-        ),
-        Commands::Replace {
-            preset,
-            config,
-            pattern,
-            replacement,
-            dir,
-            extensions,
-            exclude,
-            no_backup,
-            dry_run,
-            verbose,
-            workers,
-        } => replacer::run_replace(
-            preset,
-            config,
-            pattern,
-            replacement,
-            dir,
-            extensions,
-            exclude,
-            no_backup,
-            dry_run,
-            verbose,
-            workers,
-        ),
-        Commands::Undo { dir, keep_backups } => replacer::run_undo(dir, keep_backups),
-        Commands::CleanBackups { dir, dry_run } => replacer::run_clean_backups(dir, dry_run),
-        Commands::Rename {
-            dir,
-            pattern,
-            replacement,
-            dry_run,
-            verbose,
-            workers,
-        } => file_renamer::run_rename(dir, pattern, replacement, dry_run, verbose, workers),
+        Commands::Scan(args) => scanner::run_scan(*args),
+        Commands::Replace(args) => replacer::run_replace(*args),
+        Commands::Undo { dir, backup_dir, keep_backups, all } => {
+            replacer::run_undo(dir, backup_dir, keep_backups, all)
+        }
+        Commands::CleanBackups { dir, backup_dir, dry_run } => {
+            replacer::run_clean_backups(dir, backup_dir, dry_run)
+        }
+        Commands::Rename(args) => file_renamer::run_rename(args),
+        Commands::Format(args) => output_formatter::run_format(args),
+        Commands::Bench(args) => scanner::run_bench(args),
+        Commands::Explain(args) => explain::run_explain(args),
+        Commands::Duplicates(args) => duplicates::run_duplicates(args),
+        Commands::Extensions(args) => extensions::run_extensions(args),
+        Commands::Merge(args) => output_formatter::run_merge(args),
     }
 }
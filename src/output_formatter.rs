@@ -1,8 +1,12 @@
+use crate::cli::{FormatArgs, MergeArgs};
 use crate::scanner::Match;
-use crate::errors::Result;
-use serde::Serialize;
-use std::io::Write;
-use std::path::PathBuf;
+use crate::errors::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use chrono::{DateTime, Utc};
 
 /// Defines the possible output formats for scan results.
@@ -32,6 +36,65 @@ impl From<&str> for OutputFormat {
     }
 }
 
+/// What a scan covered, shown in the headers of the JSON and HTML reports.
+#[derive(Debug, Clone, Default)]
+pub struct RunMetadata {
+    /// The input paths that were scanned.
+    pub roots: Vec<PathBuf>,
+    /// The patterns file (or `--patterns-dir` directory), or `None` when inline `-e`
+    /// patterns were used.
+    pub patterns_file: Option<PathBuf>,
+    /// The total number of files scanned (including ones served from the cache).
+    pub files_scanned: usize,
+    /// The number of files skipped for exceeding `--max-filesize`, shown in the summary.
+    pub oversized_files: usize,
+    /// The files whose matches were cut off by `--max-per-file`, listed in the JSON and
+    /// SARIF reports and counted in the summary.
+    pub capped_files: Vec<PathBuf>,
+    /// How many times a pattern reached `--max-count` in a file, shown in the summary.
+    pub capped_patterns: usize,
+    /// How many matches there were before `--head` cut the output short, if it did.
+    pub truncated_from: Option<usize>,
+}
+
+/// The rows emitted by the `Csv` format.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsvMode {
+    /// One row per match.
+    #[default]
+    Matches,
+    /// One `Pattern,Count,FileCount` row per pattern.
+    Summary,
+}
+
+/// How many patterns the text summary lists under "Top patterns".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryTop {
+    /// List the `n` patterns with the most matches.
+    Count(usize),
+    /// List every pattern.
+    All,
+}
+
+impl Default for SummaryTop {
+    fn default() -> Self {
+        SummaryTop::Count(10)
+    }
+}
+
+impl std::str::FromStr for SummaryTop {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(SummaryTop::All);
+        }
+        s.parse()
+            .map(SummaryTop::Count)
+            .map_err(|_| format!("expected a number or `all`, got `{s}`"))
+    }
+}
+
 /// A trait for types that can format scan matches into a string.
 ///
 /// This is not currently used but could be part of a future refactoring to
@@ -47,6 +110,14 @@ pub trait Formatter {
 pub struct OutputFormatter {
     format: OutputFormat,
     include_summary: bool,
+    summary_top: SummaryTop,
+    csv_mode: CsvMode,
+    metadata: RunMetadata,
+    compact: bool,
+    summary_only: bool,
+    group_separator: Option<String>,
+    match_marker: String,
+    posix_paths: bool,
     tool_name: String,
     tool_version: String,
 }
@@ -63,10 +134,76 @@ impl OutputFormatter {
         Self {
             format,
             include_summary,
+            summary_top: SummaryTop::default(),
+            csv_mode: CsvMode::default(),
+            metadata: RunMetadata::default(),
+            compact: false,
+            summary_only: false,
+            group_separator: Some("--".to_string()),
+            match_marker: String::new(),
+            posix_paths: false,
             tool_name: "oober".to_string(),
             tool_version: env!("CARGO_PKG_VERSION").to_string(),
         }
     }
+
+    /// Sets how many patterns the summary lists under "Top patterns" (default: 10).
+    pub fn with_summary_top(mut self, summary_top: SummaryTop) -> Self {
+        self.summary_top = summary_top;
+        self
+    }
+
+    /// Sets whether the `Csv` format emits one row per match or per pattern.
+    pub fn with_csv_mode(mut self, csv_mode: CsvMode) -> Self {
+        self.csv_mode = csv_mode;
+        self
+    }
+
+    /// Sets the run metadata (scanned roots, patterns file, file count) for report headers.
+    pub fn with_metadata(mut self, metadata: RunMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Sets whether the `Json` and `Sarif` formats are written on a single line
+    /// instead of pretty-printed (default: pretty).
+    pub fn with_compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Sets whether only the summary is written, without the individual matches
+    /// (default: false). Supported by the `Text`, `Json` and `Csv` formats.
+    pub fn with_summary_only(mut self, summary_only: bool) -> Self {
+        self.summary_only = summary_only;
+        self
+    }
+
+    /// Sets the line the `Text` format writes between context groups that are not
+    /// adjacent, or `None` to write nothing (default: `--`, like grep).
+    pub fn with_group_separator(mut self, group_separator: Option<String>) -> Self {
+        self.group_separator = group_separator;
+        self
+    }
+
+    /// Sets the prefix the `Text` format puts before matching lines when context lines
+    /// are shown, to set them apart from the context (default: none, like grep).
+    pub fn with_match_marker(mut self, match_marker: impl Into<String>) -> Self {
+        self.match_marker = match_marker.into();
+        self
+    }
+
+    /// Sets whether file paths are written with `/` separators on every platform
+    /// (default: false, the platform's own). SARIF URIs always use `/`.
+    pub fn with_posix_paths(mut self, posix_paths: bool) -> Self {
+        self.posix_paths = posix_paths;
+        self
+    }
+
+    /// Formats `path` for the output, with `/` separators if `posix_paths` is set.
+    fn path(&self, path: &Path) -> String {
+        display_path(path, self.posix_paths)
+    }
     
     /// Writes the formatted scan results to a given writer.
     ///
@@ -79,18 +216,34 @@ impl OutputFormatter {
         writer: &mut W,
         matches: &[Match],
     ) -> Result<()> {
+        if self.summary_only {
+            let summary = match self.format {
+                OutputFormat::Text => self.format_summary(matches)?.trim_start().to_string(),
+                OutputFormat::Json => self.format_json(matches)?,
+                OutputFormat::Csv => self.format_csv_summary(matches)?,
+                OutputFormat::Sarif | OutputFormat::Html => {
+                    return Err("--summary-only supports the text, json and csv formats".into());
+                }
+            };
+            writer.write_all(summary.as_bytes())?;
+            return Ok(());
+        }
+
         let output = match self.format {
-            OutputFormat::Text => self.format_text(&matches)?,
-            OutputFormat::Json => self.format_json(&matches)?,
-            OutputFormat::Csv => self.format_csv(&matches)?,
-            OutputFormat::Sarif => self.format_sarif(&matches)?,
-            OutputFormat::Html => self.format_html(&matches)?,
+            OutputFormat::Text => self.format_text(matches)?,
+            OutputFormat::Json => self.format_json(matches)?,
+            OutputFormat::Csv => match self.csv_mode {
+                CsvMode::Matches => self.format_csv(matches)?,
+                CsvMode::Summary => self.format_csv_summary(matches)?,
+            },
+            OutputFormat::Sarif => self.format_sarif(matches)?,
+            OutputFormat::Html => self.format_html(matches)?,
         };
         
         writer.write_all(output.as_bytes())?;
         
         if self.include_summary && matches!(self.format, OutputFormat::Text) {
-            let summary = self.format_summary(&matches)?;
+            let summary = self.format_summary(matches)?;
             writer.write_all(summary.as_bytes())?;
         }
         
@@ -98,17 +251,55 @@ impl OutputFormatter {
     }
     
     /// Formats matches into a simple, human-readable text format.
+    ///
+    /// Context lines are written as `file-line- content`, like grep, with the group
+    /// separator (`--` by default) between groups of lines that are not adjacent and the
+    /// match marker before each matching line.
     fn format_text(&self, matches: &[Match]) -> Result<String> {
         let mut output = String::new();
-        
+        let with_context = matches
+            .iter()
+            .any(|m| !m.context_before.is_empty() || !m.context_after.is_empty());
+        let mut previous: Option<(&Path, usize)> = None;
+
         for m in matches {
+            let first_line = m.line_number - m.context_before.len();
+            if with_context
+                && let Some((path, last_line)) = previous
+                && (path != m.file_path || first_line > last_line + 1)
+                && let Some(separator) = &self.group_separator
+            {
+                output.push_str(separator);
+                output.push('\n');
+            }
+            let context_line = |output: &mut String, line_number: usize, content: &str| {
+                let path = self.path(&m.file_path);
+                output.push_str(&format!("{path}-{line_number}- {content}\n"));
+            };
+
+            for (i, line) in m.context_before.iter().enumerate() {
+                context_line(&mut output, first_line + i, line);
+            }
+            let marker = if with_context { self.match_marker.as_str() } else { "" };
             output.push_str(&format!(
-                "[{}] {}:{}: {}\n",
+                "{}[{}] {}:{}: {}\n",
+                marker,
                 m.pattern_name,
-                m.file_path.display(),
+                self.path(&m.file_path),
                 m.line_number,
                 m.line_content
             ));
+            for (i, line) in m.context_after.iter().enumerate() {
+                context_line(&mut output, m.line_number + 1 + i, line);
+            }
+            previous = Some((&m.file_path, m.line_number + m.context_after.len()));
+        }
+        if let Some(total) = self.metadata.truncated_from {
+            output.push_str(&format!(
+                "... truncated by --head: showing {} of {} matches\n",
+                matches.len(),
+                total
+            ));
         }
         
         Ok(output)
@@ -120,8 +311,33 @@ impl OutputFormatter {
         struct JsonOutput {
             tool: ToolInfo,
             scan_time: DateTime<Utc>,
+            roots: Vec<String>,
+            patterns_file: Option<String>,
+            files_scanned: usize,
             total_matches: usize,
-            matches: Vec<JsonMatch>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            truncated_from: Option<usize>,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            capped_files: Vec<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            summary: Option<JsonSummary>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            matches: Option<Vec<JsonMatch>>,
+        }
+
+        #[derive(Serialize)]
+        struct JsonSummary {
+            files_with_matches: usize,
+            by_pattern: BTreeMap<String, usize>,
+            oversized_files: usize,
+            capped_patterns: usize,
+            by_extension: BTreeMap<String, JsonExtensionCount>,
+        }
+
+        #[derive(Serialize)]
+        struct JsonExtensionCount {
+            files: usize,
+            matches: usize,
         }
         
         #[derive(Serialize)]
@@ -135,20 +351,55 @@ impl OutputFormatter {
             pattern: String,
             file: String,
             line: usize,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            column_start: Option<usize>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            column_end: Option<usize>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            byte_start: Option<u64>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            byte_end: Option<u64>,
+            #[serde(skip_serializing_if = "String::is_empty")]
+            matched_text: String,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            captures: Vec<Option<String>>,
             content: String,
             severity: String,
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            fields: BTreeMap<String, String>,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            context_before: Vec<String>,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            context_after: Vec<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            author: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            commit: Option<String>,
         }
         
-        let json_matches: Vec<JsonMatch> = matches
-            .iter()
-            .map(|m| JsonMatch {
-                pattern: m.pattern_name.clone(),
-                file: m.file_path.display().to_string(),
-                line: m.line_number,
-                content: m.line_content.trim().to_string(),
-                severity: self.get_severity(&m.pattern_name),
-            })
-            .collect();
+        let json_matches = (!self.summary_only).then(|| {
+            matches
+                .iter()
+                .map(|m| JsonMatch {
+                    pattern: m.pattern_name.clone(),
+                    file: self.path(&m.file_path),
+                    line: m.line_number,
+                    column_start: m.column_start,
+                    column_end: m.column_end,
+                    byte_start: m.byte_start,
+                    byte_end: m.byte_end,
+                    matched_text: m.matched_text.clone(),
+                    captures: m.captures.clone(),
+                    content: m.line_content.trim().to_string(),
+                    severity: self.get_severity(&m.pattern_name),
+                    fields: m.fields.clone(),
+                    context_before: m.context_before.clone(),
+                    context_after: m.context_after.clone(),
+                    author: m.author.clone(),
+                    commit: m.commit.clone(),
+                })
+                .collect()
+        });
         
         let output = JsonOutput {
             tool: ToolInfo {
@@ -156,11 +407,32 @@ impl OutputFormatter {
                 version: self.tool_version.clone(),
             },
             scan_time: Utc::now(),
+            roots: self.metadata.roots.iter().map(|p| self.path(p)).collect(),
+            patterns_file: self.metadata.patterns_file.as_ref().map(|p| self.path(p)),
+            files_scanned: self.metadata.files_scanned,
             total_matches: matches.len(),
+            truncated_from: self.metadata.truncated_from,
+            capped_files: self.metadata.capped_files.iter().map(|p| self.path(p)).collect(),
+            summary: (self.include_summary || self.summary_only).then(|| JsonSummary {
+                files_with_matches: count_files(matches),
+                by_pattern: count_by_pattern(matches)
+                    .into_iter()
+                    .map(|count| (count.pattern.to_string(), count.matches))
+                    .collect(),
+                oversized_files: self.metadata.oversized_files,
+                capped_patterns: self.metadata.capped_patterns,
+                by_extension: count_by_extension(matches)
+                    .into_iter()
+                    .map(|count| {
+                        let ExtensionCount { extension, matches, files } = count;
+                        (extension, JsonExtensionCount { files, matches })
+                    })
+                    .collect(),
+            }),
             matches: json_matches,
         };
         
-        Ok(serde_json::to_string_pretty(&output)?)
+        self.to_json(&output)
     }
     
     /// Formats matches into a CSV table.
@@ -169,17 +441,53 @@ impl OutputFormatter {
         
         let mut wtr = Writer::from_writer(vec![]);
         
+        // Captured fields become extra columns, left empty for matches without them
+        let field_names: BTreeSet<&str> = matches
+            .iter()
+            .flat_map(|m| m.fields.keys().map(String::as_str))
+            .collect();
+        
         // Write header
-        wtr.write_record(&["Pattern", "File", "Line", "Content", "Severity"])?;
+        let mut header =
+            vec!["Pattern", "File", "Line", "Content", "Severity", "StartColumn", "EndColumn"];
+        header.extend(&field_names);
+        wtr.write_record(&header)?;
         
         // Write records
         for m in matches {
-            wtr.write_record(&[
-                &m.pattern_name,
-                &m.file_path.display().to_string(),
-                &m.line_number.to_string(),
-                m.line_content.trim(),
-                &self.get_severity(&m.pattern_name),
+            let mut record = vec![
+                m.pattern_name.clone(),
+                self.path(&m.file_path),
+                m.line_number.to_string(),
+                m.line_content.trim().to_string(),
+                self.get_severity(&m.pattern_name),
+                m.column_start.map(|c| c.to_string()).unwrap_or_default(),
+                m.column_end.map(|c| c.to_string()).unwrap_or_default(),
+            ];
+            record.extend(
+                field_names
+                    .iter()
+                    .map(|name| m.fields.get(*name).cloned().unwrap_or_default()),
+            );
+            wtr.write_record(&record)?;
+        }
+        
+        let data = wtr.into_inner().map_err(|e| format!("CSV writer error: {}", e))?;
+        Ok(String::from_utf8(data).unwrap_or_default())
+    }
+    
+    /// Formats per-pattern totals as CSV, one `Pattern,Count,FileCount` row per pattern.
+    fn format_csv_summary(&self, matches: &[Match]) -> Result<String> {
+        use csv::Writer;
+        
+        let mut wtr = Writer::from_writer(vec![]);
+        wtr.write_record(["Pattern", "Count", "FileCount"])?;
+        
+        for count in count_by_pattern(matches) {
+            wtr.write_record([
+                count.pattern,
+                &count.matches.to_string(),
+                &count.files.to_string(),
             ])?;
         }
         
@@ -202,7 +510,16 @@ impl OutputFormatter {
         #[serde(rename_all = "camelCase")]
         struct Run {
             tool: Tool,
+            column_kind: String,
             results: Vec<SarifResult>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            properties: Option<RunProperties>,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct RunProperties {
+            capped_files: Vec<String>,
         }
         
         #[derive(Serialize)]
@@ -275,6 +592,10 @@ impl OutputFormatter {
         #[serde(rename_all = "camelCase")]
         struct Region {
             start_line: usize,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            start_column: Option<usize>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            end_column: Option<usize>,
             snippet: Snippet,
         }
         
@@ -317,10 +638,12 @@ impl OutputFormatter {
                 locations: vec![Location {
                     physical_location: PhysicalLocation {
                         artifact_location: ArtifactLocation {
-                            uri: m.file_path.display().to_string(),
+                            uri: display_path(&m.file_path, true),
                         },
                         region: Region {
                             start_line: m.line_number,
+                            start_column: m.column_start,
+                            end_column: m.column_end,
                             snippet: Snippet {
                                 text: m.line_content.trim().to_string(),
                             },
@@ -341,11 +664,22 @@ impl OutputFormatter {
                         rules,
                     },
                 },
+                // Match columns count characters, not UTF-16 code units
+                column_kind: "unicodeCodePoints".to_string(),
                 results,
+                // SARIF has no place for a truncated result set, so it goes in a property bag
+                properties: (!self.metadata.capped_files.is_empty()).then(|| RunProperties {
+                    capped_files: self
+                        .metadata
+                        .capped_files
+                        .iter()
+                        .map(|p| display_path(p, true))
+                        .collect(),
+                }),
             }],
         };
         
-        Ok(serde_json::to_string_pretty(&output)?)
+        self.to_json(&output)
     }
     
     /// Formats matches into a rich HTML report.
@@ -379,12 +713,33 @@ impl OutputFormatter {
         <strong>Total Matches:</strong> "#);
         
         html.push_str(&matches.len().to_string());
+        if let Some(total) = self.metadata.truncated_from {
+            html.push_str(&format!(" (truncated by --head from {total})"));
+        }
         html.push_str(r#"<br>
         <strong>Scan Time:</strong> "#);
         html.push_str(&Utc::now().to_rfc3339());
         html.push_str(r#"<br>
         <strong>Tool Version:</strong> "#);
         html.push_str(&self.tool_version);
+        html.push_str(r#"<br>
+        <strong>Scanned:</strong> "#);
+        let roots: Vec<String> = self
+            .metadata
+            .roots
+            .iter()
+            .map(|p| html_escape(&self.path(p)))
+            .collect();
+        html.push_str(&roots.join(", "));
+        html.push_str(r#"<br>
+        <strong>Patterns File:</strong> "#);
+        match &self.metadata.patterns_file {
+            Some(path) => html.push_str(&html_escape(&self.path(path))),
+            None => html.push_str("inline patterns"),
+        }
+        html.push_str(r#"<br>
+        <strong>Files Scanned:</strong> "#);
+        html.push_str(&self.metadata.files_scanned.to_string());
         html.push_str(r#"
     </div>
     
@@ -413,7 +768,7 @@ impl OutputFormatter {
                 <td class="{}">{}</td>
             </tr>"#,
                 html_escape(&m.pattern_name),
-                html_escape(&m.file_path.display().to_string()),
+                html_escape(&self.path(&m.file_path)),
                 m.line_number,
                 html_escape(m.line_content.trim()),
                 severity_class,
@@ -432,33 +787,62 @@ impl OutputFormatter {
     
     /// Generates a summary of scan results, including counts and top patterns.
     fn format_summary(&self, matches: &[Match]) -> Result<String> {
-        use std::collections::HashMap;
-        
-        let mut pattern_counts: HashMap<String, usize> = HashMap::new();
-        let mut file_counts: HashMap<PathBuf, usize> = HashMap::new();
-        
-        for m in matches {
-            *pattern_counts.entry(m.pattern_name.clone()).or_insert(0) += 1;
-            *file_counts.entry(m.file_path.clone()).or_insert(0) += 1;
-        }
+        let pattern_counts = count_by_pattern(matches);
         
         let mut summary = String::new();
         summary.push_str(&format!("\n{} Summary {}\n", "=".repeat(20), "=".repeat(20)));
         summary.push_str(&format!("Total matches: {}\n", matches.len()));
-        summary.push_str(&format!("Files with matches: {}\n", file_counts.len()));
+        summary.push_str(&format!("Files with matches: {}\n", count_files(matches)));
+        if self.metadata.oversized_files > 0 {
+            summary.push_str(&format!(
+                "Files skipped (over --max-filesize): {}\n",
+                self.metadata.oversized_files
+            ));
+        }
+        if !self.metadata.capped_files.is_empty() {
+            summary.push_str(&format!(
+                "Files capped (at --max-per-file): {}\n",
+                self.metadata.capped_files.len()
+            ));
+        }
+        if self.metadata.capped_patterns > 0 {
+            summary.push_str(&format!(
+                "Patterns capped in a file (at --max-count): {}\n",
+                self.metadata.capped_patterns
+            ));
+        }
         summary.push_str(&format!("Unique patterns: {}\n\n", pattern_counts.len()));
         
         summary.push_str("Top patterns:\n");
-        let mut patterns: Vec<_> = pattern_counts.iter().collect();
-        patterns.sort_by(|a, b| b.1.cmp(a.1));
+        let top = match self.summary_top {
+            SummaryTop::Count(n) => n,
+            SummaryTop::All => pattern_counts.len(),
+        };
         
-        for (pattern, count) in patterns.iter().take(10) {
-            summary.push_str(&format!("  {} - {} matches\n", pattern, count));
+        for count in pattern_counts.iter().take(top) {
+            summary.push_str(&format!("  {} - {} matches\n", count.pattern, count.matches));
+        }
+        
+        summary.push_str("\nBy extension:\n");
+        for count in count_by_extension(matches) {
+            summary.push_str(&format!(
+                "  {} - {} matches in {} files\n",
+                count.extension, count.matches, count.files
+            ));
         }
         
         Ok(summary)
     }
     
+    /// Serializes a report, pretty-printed unless compact output was requested.
+    fn to_json<T: Serialize>(&self, value: &T) -> Result<String> {
+        if self.compact {
+            Ok(serde_json::to_string(value)?)
+        } else {
+            Ok(serde_json::to_string_pretty(value)?)
+        }
+    }
+    
     /// Determines a severity level based on keywords in a pattern's name.
     ///
     /// # Optimization Note
@@ -487,6 +871,272 @@ impl OutputFormatter {
     }
 }
 
+/// Match totals for one pattern, shared by the text and CSV summaries.
+struct PatternCount<'a> {
+    pattern: &'a str,
+    matches: usize,
+    files: usize,
+}
+
+/// Counts matches and distinct files per pattern, most frequent pattern first.
+fn count_by_pattern(matches: &[Match]) -> Vec<PatternCount<'_>> {
+    let mut by_pattern: HashMap<&str, (usize, HashSet<&Path>)> = HashMap::new();
+    for m in matches {
+        let (count, files) = by_pattern.entry(m.pattern_name.as_str()).or_default();
+        *count += 1;
+        files.insert(m.file_path.as_path());
+    }
+    
+    let mut counts: Vec<_> = by_pattern
+        .into_iter()
+        .map(|(pattern, (matches, files))| PatternCount { pattern, matches, files: files.len() })
+        .collect();
+    counts.sort_by(|a, b| b.matches.cmp(&a.matches).then_with(|| a.pattern.cmp(b.pattern)));
+    counts
+}
+
+/// Match totals for one file extension, shared by the text and JSON summaries.
+struct ExtensionCount {
+    /// The lowercased extension without its dot, or `(none)`.
+    extension: String,
+    matches: usize,
+    files: usize,
+}
+
+/// Counts matches and distinct files per file extension, most frequent first.
+fn count_by_extension(matches: &[Match]) -> Vec<ExtensionCount> {
+    let mut by_extension: HashMap<String, (usize, HashSet<&Path>)> = HashMap::new();
+    for m in matches {
+        let extension = m
+            .file_path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "(none)".to_string());
+        let (count, files) = by_extension.entry(extension).or_default();
+        *count += 1;
+        files.insert(m.file_path.as_path());
+    }
+
+    let mut counts: Vec<_> = by_extension
+        .into_iter()
+        .map(|(extension, (matches, files))| ExtensionCount {
+            extension,
+            matches,
+            files: files.len(),
+        })
+        .collect();
+    counts.sort_by(|a, b| b.matches.cmp(&a.matches).then_with(|| a.extension.cmp(&b.extension)));
+    counts
+}
+
+/// Formats `path` for output. With `posix`, the platform's separator is replaced by `/`
+/// (a no-op where it already is `/`), as tools and SARIF URIs expect.
+pub fn display_path(path: &Path, posix: bool) -> String {
+    let path = path.display().to_string();
+    if posix && std::path::MAIN_SEPARATOR != '/' {
+        path.replace(std::path::MAIN_SEPARATOR, "/")
+    } else {
+        path
+    }
+}
+
+/// Counts the distinct files among `matches`.
+fn count_files(matches: &[Match]) -> usize {
+    matches.iter().map(|m| m.file_path.as_path()).collect::<HashSet<_>>().len()
+}
+
+/// The main entry point for the `format` command.
+///
+/// Reads matches from a JSON Lines file and writes them in the requested output
+/// format, so saved scan results can be re-rendered without scanning again.
+pub fn run_format(args: FormatArgs) -> Result<()> {
+    let FormatArgs {
+        from,
+        to,
+        format,
+        include_summary,
+    } = args;
+
+    let matches = read_matches_jsonl(&from)?;
+
+    // An explicit --format wins; otherwise use the extension of --to
+    let format = format
+        .or_else(|| {
+            to.as_deref()
+                .and_then(|path| path.extension())
+                .and_then(|ext| ext.to_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_default();
+    let formatter = OutputFormatter::new(OutputFormat::from(format.as_str()), include_summary);
+
+    let mut writer: Box<dyn Write> = match to {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+    formatter.write_output(&mut writer, &matches)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// The main entry point for the `merge` command.
+///
+/// Reads the JSON results of several scans (e.g. of the shards of a monorepo) and writes
+/// their matches as one report, keeping a match that appears in several inputs once.
+pub fn run_merge(args: MergeArgs) -> Result<()> {
+    let MergeArgs {
+        inputs,
+        output,
+        format,
+        include_summary,
+    } = args;
+
+    let (matches, metadata) = merge_results(&inputs)?;
+
+    let format = format
+        .or_else(|| {
+            output
+                .as_deref()
+                .and_then(|path| path.extension())
+                .and_then(|ext| ext.to_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "json".to_string());
+    let formatter = OutputFormatter::new(OutputFormat::from(format.as_str()), include_summary)
+        .with_metadata(metadata);
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+    formatter.write_output(&mut writer, &matches)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// A scan result file read by `merge`: a `json` report, or a bare array of matches.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ResultFile {
+    Report {
+        #[serde(default)]
+        roots: Vec<PathBuf>,
+        #[serde(default)]
+        patterns_file: Option<PathBuf>,
+        #[serde(default)]
+        files_scanned: usize,
+        matches: Vec<Match>,
+    },
+    Matches(Vec<Match>),
+}
+
+/// Reads and concatenates the matches of `inputs`, dropping matches with the same
+/// pattern, file, line and content as an earlier one. The metadata lists every root,
+/// the first patterns file, and the sum of the files scanned.
+fn merge_results(inputs: &[PathBuf]) -> Result<(Vec<Match>, RunMetadata)> {
+    let mut matches: Vec<Match> = Vec::new();
+    let mut metadata = RunMetadata::default();
+    let mut seen = HashSet::new();
+
+    for input in inputs {
+        let file = File::open(input).map_err(|e| Error::from(e).for_file(input))?;
+        let reader = BufReader::new(file);
+        let file: ResultFile = serde_json::from_reader(reader)
+            .map_err(|e| format!("{}: not a JSON scan result: {}", input.display(), e))?;
+        let file_matches = match file {
+            ResultFile::Report { roots, patterns_file, files_scanned, matches } => {
+                for root in roots {
+                    if !metadata.roots.contains(&root) {
+                        metadata.roots.push(root);
+                    }
+                }
+                metadata.patterns_file = metadata.patterns_file.or(patterns_file);
+                metadata.files_scanned += files_scanned;
+                matches
+            }
+            ResultFile::Matches(matches) => matches,
+        };
+
+        for m in file_matches {
+            // The `json` format trims the line content, so compare it trimmed
+            let key = (
+                m.pattern_name.clone(),
+                m.file_path.clone(),
+                m.line_number,
+                m.line_content.trim().to_string(),
+            );
+            if seen.insert(key) {
+                matches.push(m);
+            }
+        }
+    }
+
+    Ok((matches, metadata))
+}
+
+/// Reads one JSON-encoded `Match` per line, skipping blank lines.
+fn read_matches_jsonl(path: &Path) -> Result<Vec<Match>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut matches = Vec::new();
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let m = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: invalid match record: {}", path.display(), i + 1, e))?;
+        matches.push(m);
+    }
+
+    Ok(matches)
+}
+
+/// Writes the text of each match to `<dir>/<pattern>.txt`, one match per line, for
+/// `scan --split-by-pattern`. Matches are ordered by file, line and column so the
+/// files do not depend on the order the scan finished in. Characters that are not
+/// safe in file names are replaced with `_` in the pattern name.
+pub fn write_split_by_pattern(dir: &Path, matches: &[Match]) -> Result<()> {
+    std::fs::create_dir_all(dir).map_err(|e| Error::from(e).for_file(dir))?;
+
+    let mut by_pattern: BTreeMap<&str, Vec<&Match>> = BTreeMap::new();
+    for m in matches {
+        by_pattern.entry(&m.pattern_name).or_default().push(m);
+    }
+
+    for (pattern, mut group) in by_pattern {
+        group.sort_by(|a, b| {
+            (&a.file_path, a.line_number, a.column_start)
+                .cmp(&(&b.file_path, b.line_number, b.column_start))
+        });
+        let name: String = pattern
+            .chars()
+            .map(|c| if c.is_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{name}.txt"));
+        let file = File::create(&path).map_err(|e| Error::from(e).for_file(&path))?;
+        let mut writer = BufWriter::new(file);
+        for m in group {
+            writeln!(writer, "{}", matched_text(m))?;
+        }
+        writer.flush().map_err(|e| Error::from(e).for_file(&path))?;
+    }
+
+    Ok(())
+}
+
+/// The part of the line a match covers, or the whole line if its columns are unknown.
+fn matched_text(m: &Match) -> Cow<'_, str> {
+    match (m.column_start, m.column_end) {
+        (Some(start), Some(end)) => Cow::Owned(
+            m.line_content.chars().skip(start - 1).take(end.saturating_sub(start)).collect(),
+        ),
+        _ => Cow::Borrowed(&m.line_content),
+    }
+}
+
 /// Escapes a string for safe inclusion in HTML.
 fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
@@ -506,14 +1156,36 @@ mod tests {
                 pattern_name: "email".to_string(),
                 file_path: PathBuf::from("src/main.rs"),
                 line_number: 42,
+                column_start: Some(14),
+                column_end: Some(30),
+                byte_start: Some(1203),
+                byte_end: Some(1219),
+                matched_text: String::new(),
+                captures: Vec::new(),
                 line_content: "let email = \"test@example.com\";".to_string(),
-            },
+                fields: BTreeMap::new(),
+                context_before: Vec::new(),
+                context_after: Vec::new(),
+                author: None,
+                commit: None,
+},
             Match {
                 pattern_name: "api_key".to_string(),
                 file_path: PathBuf::from("config.toml"),
                 line_number: 10,
+                column_start: None,
+                column_end: None,
+                byte_start: None,
+                byte_end: None,
+                matched_text: String::new(),
+                captures: Vec::new(),
                 line_content: "api_key = \"sk-1234567890\"".to_string(),
-            },
+                fields: BTreeMap::new(),
+                context_before: Vec::new(),
+                context_after: Vec::new(),
+                author: None,
+                commit: None,
+},
         ]
     }
     
@@ -527,6 +1199,38 @@ mod tests {
         
         assert_eq!(parsed["total_matches"], 2);
         assert_eq!(parsed["matches"][0]["pattern"], "email");
+        assert_eq!(parsed["matches"][0]["column_start"], 14);
+        assert_eq!(parsed["matches"][0]["column_end"], 30);
+        assert_eq!(parsed["matches"][0]["byte_start"], 1203);
+        assert_eq!(parsed["matches"][0]["byte_end"], 1219);
+        assert!(parsed["matches"][1].get("column_start").is_none());
+        assert!(parsed["matches"][1].get("byte_start").is_none());
+    }
+    
+    #[test]
+    fn test_compact_json_and_sarif() {
+        let matches = create_test_matches();
+        let pretty = OutputFormatter::new(OutputFormat::Json, false);
+        let compact = OutputFormatter::new(OutputFormat::Json, false).with_compact(true);
+        
+        for format in [OutputFormatter::format_json, OutputFormatter::format_sarif] {
+            let pretty_output = format(&pretty, &matches).unwrap();
+            let compact_output = format(&compact, &matches).unwrap();
+            assert!(pretty_output.contains('\n'));
+            assert!(!compact_output.contains('\n'));
+            assert!(compact_output.len() < pretty_output.len());
+            
+            let mut pretty_value: serde_json::Value = serde_json::from_str(&pretty_output).unwrap();
+            let mut compact_value: serde_json::Value =
+                serde_json::from_str(&compact_output).unwrap();
+            // Only the timestamp differs between the two runs
+            for value in [&mut pretty_value, &mut compact_value] {
+                if let Some(object) = value.as_object_mut() {
+                    object.remove("scan_time");
+                }
+            }
+            assert_eq!(pretty_value, compact_value);
+        }
     }
     
     #[test]
@@ -548,6 +1252,57 @@ mod tests {
         assert_eq!(records.len(), 2);
     }
     
+    #[test]
+    fn test_csv_summary_mode() {
+        let mut matches = create_test_matches();
+        matches.push(Match {
+            pattern_name: "email".to_string(),
+            file_path: PathBuf::from("README.md"),
+            line_number: 3,
+            column_start: None,
+            column_end: None,
+            byte_start: None,
+            byte_end: None,
+            matched_text: String::new(),
+            captures: Vec::new(),
+            line_content: "contact: dev@example.com".to_string(),
+            fields: BTreeMap::new(),
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+            author: None,
+            commit: None,
+});
+        matches.push(Match {
+            pattern_name: "email".to_string(),
+            file_path: PathBuf::from("README.md"),
+            line_number: 4,
+            column_start: None,
+            column_end: None,
+            byte_start: None,
+            byte_end: None,
+            matched_text: String::new(),
+            captures: Vec::new(),
+            line_content: "or ops@example.com".to_string(),
+            fields: BTreeMap::new(),
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+            author: None,
+            commit: None,
+});
+        let formatter =
+            OutputFormatter::new(OutputFormat::Csv, false).with_csv_mode(CsvMode::Summary);
+        let mut output = Vec::new();
+        formatter.write_output(&mut output, &matches).unwrap();
+        
+        let mut rdr = csv::Reader::from_reader(output.as_slice());
+        assert_eq!(rdr.headers().unwrap(), vec!["Pattern", "Count", "FileCount"]);
+        let rows: Vec<Vec<String>> = rdr
+            .records()
+            .map(|r| r.unwrap().iter().map(str::to_string).collect())
+            .collect();
+        assert_eq!(rows, vec![vec!["email", "3", "2"], vec!["api_key", "1", "1"]]);
+    }
+    
     #[test]
     fn test_sarif_format() {
         let formatter = OutputFormatter::new(OutputFormat::Sarif, false);
@@ -558,6 +1313,10 @@ mod tests {
         
         assert_eq!(parsed["version"], "2.1.0");
         assert_eq!(parsed["runs"][0]["results"].as_array().unwrap().len(), 2);
+        assert_eq!(parsed["runs"][0]["columnKind"], "unicodeCodePoints");
+        let region = &parsed["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startColumn"], 14);
+        assert_eq!(region["endColumn"], 30);
     }
     
     #[test]
@@ -572,6 +1331,61 @@ mod tests {
         assert!(output.contains("sk-1234567890"));
     }
     
+    #[test]
+    fn test_run_metadata_in_json_and_html() {
+        let metadata = RunMetadata {
+            roots: vec![PathBuf::from("services/api")],
+            patterns_file: Some(PathBuf::from("patterns/secrets.yaml")),
+            files_scanned: 128,
+            oversized_files: 0,
+            capped_files: Vec::new(),
+            capped_patterns: 0,
+            truncated_from: None,
+        };
+        let matches = create_test_matches();
+        
+        let json = OutputFormatter::new(OutputFormat::Json, false).with_metadata(metadata.clone());
+        let parsed: serde_json::Value =
+            serde_json::from_str(&json.format_json(&matches).unwrap()).unwrap();
+        assert_eq!(parsed["roots"][0], "services/api");
+        assert_eq!(parsed["patterns_file"], "patterns/secrets.yaml");
+        assert_eq!(parsed["files_scanned"], 128);
+        
+        let html = OutputFormatter::new(OutputFormat::Html, false).with_metadata(metadata);
+        let output = html.format_html(&matches).unwrap();
+        assert!(output.contains("<strong>Scanned:</strong> services/api"));
+        assert!(output.contains("<strong>Files Scanned:</strong> 128"));
+    }
+    
+    #[test]
+    fn test_format_renders_jsonl_as_html() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input = temp_dir.path().join("results.jsonl");
+        let report = temp_dir.path().join("report.html");
+        let mut jsonl: String = create_test_matches()
+            .iter()
+            .map(|m| serde_json::to_string(m).unwrap() + "\n")
+            .collect();
+        // Records in the `json` output format's field names are accepted too
+        jsonl.push_str("\n{\"pattern\":\"todo\",\"file\":\"lib.rs\",\"line\":7,\"content\":\"// TODO: ship\"}\n");
+        std::fs::write(&input, jsonl).unwrap();
+        
+        run_format(FormatArgs {
+            from: input,
+            to: Some(report.clone()),
+            format: None,
+            include_summary: false,
+        })
+        .unwrap();
+        
+        let html = std::fs::read_to_string(&report).unwrap();
+        assert!(html.contains("<!DOCTYPE html>"));
+        assert!(html.contains("test@example.com"));
+        assert!(html.contains("sk-1234567890"));
+        assert!(html.contains("// TODO: ship"));
+        assert!(html.contains("<strong>Total Matches:</strong> 3"));
+    }
+    
     #[test]
     fn test_html_escaping() {
         let dangerous = "< script>alert('xss')</script>";
@@ -579,4 +1393,266 @@ mod tests {
         
         assert_eq!(escaped, "&lt; script&gt;alert(&#39;xss&#39;)&lt;/script&gt;");
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_summary_lists_top_n_patterns() {
+        let matches: Vec<Match> = (0..15)
+            .flat_map(|i| {
+                (0..=i).map(move |_| Match {
+                    pattern_name: format!("pattern_{i:02}"),
+                    file_path: PathBuf::from("src/lib.rs"),
+                    line_number: 1,
+                    column_start: None,
+                    column_end: None,
+                    byte_start: None,
+                    byte_end: None,
+                    matched_text: String::new(),
+                    captures: Vec::new(),
+                    line_content: String::new(),
+                    fields: BTreeMap::new(),
+                    context_before: Vec::new(),
+                    context_after: Vec::new(),
+                    author: None,
+                    commit: None,
+})
+            })
+            .collect();
+        let listed = |top: SummaryTop| {
+            let formatter = OutputFormatter::new(OutputFormat::Text, true).with_summary_top(top);
+            let summary = formatter.format_summary(&matches).unwrap();
+            summary
+                .lines()
+                .filter(|line| line.starts_with("  pattern_"))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        
+        let top3 = listed(SummaryTop::Count(3));
+        assert_eq!(top3.len(), 3);
+        assert_eq!(top3[0], "  pattern_14 - 15 matches");
+        assert_eq!(listed(SummaryTop::default()).len(), 10);
+        assert_eq!(listed(SummaryTop::All).len(), 15);
+        assert_eq!("all".parse::<SummaryTop>(), Ok(SummaryTop::All));
+        assert!("ten".parse::<SummaryTop>().is_err());
+    }
+
+    #[test]
+    fn test_summary_counts_by_extension() {
+        let matches: Vec<Match> = [
+            ("src/a.rs", 1),
+            ("src/a.rs", 2),
+            ("src/b.RS", 1),
+            ("web/app.ts", 4),
+            ("Makefile", 3),
+        ]
+        .into_iter()
+        .map(|(path, line)| Match {
+            pattern_name: "todo".to_string(),
+            file_path: PathBuf::from(path),
+            line_number: line,
+            column_start: None,
+            column_end: None,
+            byte_start: None,
+            byte_end: None,
+            matched_text: String::new(),
+            captures: Vec::new(),
+            line_content: String::new(),
+            fields: BTreeMap::new(),
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+            author: None,
+            commit: None,
+        })
+        .collect();
+
+        let formatter = OutputFormatter::new(OutputFormat::Text, true);
+        let summary = formatter.format_summary(&matches).unwrap();
+        let by_extension: Vec<&str> =
+            summary.split("By extension:\n").nth(1).unwrap().lines().collect();
+        assert_eq!(
+            by_extension,
+            [
+                "  rs - 3 matches in 2 files",
+                "  (none) - 1 matches in 1 files",
+                "  ts - 1 matches in 1 files",
+            ]
+        );
+
+        let json: serde_json::Value = serde_json::from_str(
+            &OutputFormatter::new(OutputFormat::Json, true).format_json(&matches).unwrap(),
+        )
+        .unwrap();
+        let by_extension = &json["summary"]["by_extension"];
+        assert_eq!(by_extension["rs"]["files"], 2);
+        assert_eq!(by_extension["rs"]["matches"], 3);
+        assert_eq!(by_extension["ts"]["matches"], 1);
+        assert_eq!(json["summary"]["files_with_matches"], 4);
+
+        let plain = OutputFormatter::new(OutputFormat::Json, false).format_json(&matches).unwrap();
+        assert!(!plain.contains("\"summary\""));
+    }
+    
+    #[test]
+    fn test_merge_combines_json_results() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let [email, api_key] = <[Match; 2]>::try_from(create_test_matches()).unwrap();
+        let todo = Match {
+            pattern_name: "todo".to_string(),
+            file_path: PathBuf::from("lib/util.rs"),
+            line_number: 3,
+            column_start: None,
+            column_end: None,
+            byte_start: None,
+            byte_end: None,
+            matched_text: String::new(),
+            captures: Vec::new(),
+            line_content: "    // TODO: split".to_string(),
+            fields: BTreeMap::new(),
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+            author: None,
+            commit: None,
+        };
+        let shards = [
+            ("a.json", "src", 12, vec![email.clone(), todo.clone()]),
+            // The overlapping match is reported by both shards
+            ("b.json", "lib", 8, vec![api_key, todo]),
+        ];
+        let mut inputs = Vec::new();
+        for (name, root, files_scanned, matches) in shards {
+            let metadata = RunMetadata {
+                roots: vec![PathBuf::from(root)],
+                patterns_file: Some(PathBuf::from("patterns.yaml")),
+                files_scanned,
+                oversized_files: 0,
+                capped_files: Vec::new(),
+                capped_patterns: 0,
+                truncated_from: None,
+            };
+            let json = OutputFormatter::new(OutputFormat::Json, false)
+                .with_metadata(metadata)
+                .format_json(&matches)
+                .unwrap();
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, json).unwrap();
+            inputs.push(path);
+        }
+        let combined = temp_dir.path().join("combined.json");
+
+        run_merge(MergeArgs {
+            inputs,
+            output: Some(combined.clone()),
+            format: None,
+            include_summary: false,
+        })
+        .unwrap();
+
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&combined).unwrap()).unwrap();
+        assert_eq!(report["total_matches"], 3);
+        assert_eq!(report["files_scanned"], 20);
+        assert_eq!(report["roots"], serde_json::json!(["src", "lib"]));
+        let patterns: Vec<&str> = report["matches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["pattern"].as_str().unwrap())
+            .collect();
+        assert_eq!(patterns, ["email", "todo", "api_key"]);
+    }
+
+    #[test]
+    fn test_text_context_separator_and_match_marker() {
+        let context_match = |line: usize, before: &[&str], after: &[&str]| Match {
+            pattern_name: "todo".to_string(),
+            file_path: PathBuf::from("a.txt"),
+            line_number: line,
+            column_start: None,
+            column_end: None,
+            byte_start: None,
+            byte_end: None,
+            matched_text: String::new(),
+            captures: Vec::new(),
+            line_content: format!("TODO {line}"),
+            fields: BTreeMap::new(),
+            context_before: before.iter().map(|s| s.to_string()).collect(),
+            context_after: after.iter().map(|s| s.to_string()).collect(),
+            author: None,
+            commit: None,
+        };
+        // Lines 1-3 and 3-4 are adjacent; line 8 starts a new group
+        let matches = [
+            context_match(2, &["a"], &[]),
+            context_match(3, &[], &["b"]),
+            context_match(8, &["c"], &[]),
+        ];
+
+        let grep_like = OutputFormatter::new(OutputFormat::Text, false);
+        assert_eq!(
+            grep_like.format_text(&matches).unwrap(),
+            "a.txt-1- a\n[todo] a.txt:2: TODO 2\n[todo] a.txt:3: TODO 3\na.txt-4- b\n\
+             --\na.txt-7- c\n[todo] a.txt:8: TODO 8\n"
+        );
+
+        let custom = OutputFormatter::new(OutputFormat::Text, false)
+            .with_group_separator(Some("~~~".to_string()))
+            .with_match_marker("> ");
+        assert_eq!(
+            custom.format_text(&matches).unwrap(),
+            "a.txt-1- a\n> [todo] a.txt:2: TODO 2\n> [todo] a.txt:3: TODO 3\na.txt-4- b\n\
+             ~~~\na.txt-7- c\n> [todo] a.txt:8: TODO 8\n"
+        );
+
+        let unseparated =
+            OutputFormatter::new(OutputFormat::Text, false).with_group_separator(None);
+        assert!(!unseparated.format_text(&matches).unwrap().contains("--"));
+    }
+
+    #[test]
+    fn test_posix_paths_use_forward_slashes() {
+        // Joined with the platform's separator, so this is `src\nested\a.rs` on Windows
+        let path: PathBuf = ["src", "nested", "a.rs"].iter().collect();
+        let matches = vec![Match {
+            pattern_name: "todo".to_string(),
+            file_path: path.clone(),
+            line_number: 3,
+            column_start: Some(4),
+            column_end: Some(8),
+            byte_start: None,
+            byte_end: None,
+            matched_text: String::new(),
+            captures: Vec::new(),
+            line_content: "// TODO".to_string(),
+            fields: BTreeMap::new(),
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+            author: None,
+            commit: None,
+        }];
+        let metadata = RunMetadata { roots: vec![path.clone()], ..RunMetadata::default() };
+        let render = |format: OutputFormat, posix_paths: bool| {
+            let formatter = OutputFormatter::new(format, false)
+                .with_metadata(metadata.clone())
+                .with_posix_paths(posix_paths);
+            let mut output = Vec::new();
+            formatter.write_output(&mut output, &matches).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        for format in [OutputFormat::Text, OutputFormat::Json, OutputFormat::Csv] {
+            let output = render(format, true);
+            assert!(output.contains("src/nested/a.rs"), "{output}");
+            assert!(!output.contains('\\'), "{output}");
+        }
+        let json: serde_json::Value =
+            serde_json::from_str(&render(OutputFormat::Json, true)).unwrap();
+        assert_eq!(json["roots"][0], "src/nested/a.rs");
+
+        // SARIF URIs use `/` even without the option
+        let sarif: serde_json::Value =
+            serde_json::from_str(&render(OutputFormat::Sarif, false)).unwrap();
+        let uri = &sarif["runs"][0]["results"][0]["locations"][0]["physicalLocation"]
+            ["artifactLocation"]["uri"];
+        assert_eq!(uri, "src/nested/a.rs");
+    }
+}
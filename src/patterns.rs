@@ -1,5 +1,5 @@
 use crate::cli::Preset;
-use crate::config::{Block, ReplaceConfig};
+use crate::config::{Block, LineEnding, ReplaceConfig};
 use crate::errors::Result;
 use regex::Regex;
 use std::collections::HashMap;
@@ -47,6 +47,7 @@ impl PatternManager {
             Preset::RemoveCopyright => ReplaceConfig {
                 patterns: vec![],
                 replacements: vec![],
+                limits: vec![],
                 blocks: vec![
                     // Common copyright patterns
                     Block {
@@ -86,6 +87,9 @@ impl PatternManager {
                 ],
                 extensions: None,
                 exclude: None,
+                ignore_patterns: vec![],
+                multiline_anchors: false,
+                line_endings: None,
             },
 
             Preset::CleanDebug => ReplaceConfig {
@@ -111,6 +115,7 @@ impl PatternManager {
                     Some("// Debug.Print()".to_string()),
                     Some("// System.out.println(); // DEBUG".to_string()),
                 ],
+                limits: vec![],
                 blocks: vec![
                     Block {
                         start: "// DEBUG START".to_string(),
@@ -127,6 +132,9 @@ impl PatternManager {
                 ],
                 extensions: None,
                 exclude: None,
+                ignore_patterns: vec![],
+                multiline_anchors: false,
+                line_endings: None,
             },
 
             Preset::RemoveTodos => ReplaceConfig {
@@ -150,9 +158,13 @@ impl PatternManager {
                     Some("".to_string()),
                     Some("".to_string()),
                 ],
+                limits: vec![],
                 blocks: vec![],
                 extensions: None,
                 exclude: None,
+                ignore_patterns: vec![],
+                multiline_anchors: false,
+                line_endings: None,
             },
 
             Preset::TrimWhitespace => ReplaceConfig {
@@ -160,9 +172,13 @@ impl PatternManager {
                     "[ \\t]+$".to_string(), // Trailing whitespace
                 ],
                 replacements: vec![Some("".to_string())],
+                limits: vec![],
                 blocks: vec![],
                 extensions: None,
                 exclude: None,
+                ignore_patterns: vec![],
+                multiline_anchors: false,
+                line_endings: None,
             },
 
             Preset::RemoveEmptyComments => ReplaceConfig {
@@ -180,12 +196,16 @@ impl PatternManager {
                 // An empty `end` pattern is not a reliable way to define a block. A better approach
                 // would be to have a dedicated pattern that matches empty multi-line comment blocks,
                 // like `/\*\s*\*/`.
+                limits: vec![],
                 blocks: vec![Block {
                     start: "/*\n */".to_string(),
                     end: "".to_string(), // This won't work, need to handle differently
                 }],
                 extensions: None,
                 exclude: None,
+                ignore_patterns: vec![],
+                multiline_anchors: false,
+                line_endings: None,
             },
 
             Preset::TabsToSpaces => ReplaceConfig {
@@ -193,9 +213,26 @@ impl PatternManager {
                 replacements: vec![
                     Some("    ".to_string()), // 4 spaces
                 ],
+                limits: vec![],
                 blocks: vec![],
                 extensions: None,
                 exclude: None,
+                ignore_patterns: vec![],
+                multiline_anchors: false,
+                line_endings: None,
+            },
+
+            Preset::NormalizeLineEndings => ReplaceConfig {
+                patterns: vec![],
+                replacements: vec![],
+                limits: vec![],
+                blocks: vec![],
+                extensions: None,
+                exclude: None,
+                ignore_patterns: vec![],
+                multiline_anchors: false,
+                // `--line-endings crlf` converts the other way
+                line_endings: Some(LineEnding::Lf),
             },
 
             Preset::SpacesToTabs => ReplaceConfig {
@@ -203,9 +240,13 @@ impl PatternManager {
                     "    ".to_string(), // 4 spaces
                 ],
                 replacements: vec![Some("\t".to_string())],
+                limits: vec![],
                 blocks: vec![],
                 extensions: None,
                 exclude: None,
+                ignore_patterns: vec![],
+                multiline_anchors: false,
+                line_endings: None,
             },
         }
     }
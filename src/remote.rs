@@ -0,0 +1,28 @@
+//! Fetches the HTTP(S) inputs of `scan`, with the `remote` cargo feature.
+//!
+//! A URL given as a scan input is downloaded into memory and scanned like standard
+//! input, with its matches reported under the URL.
+
+use crate::errors::Result;
+use std::io::Read;
+
+/// Downloads the body of `url`, or returns `None` if it is larger than `max_bytes`.
+///
+/// The size is checked against the `Content-Length` header first, and the download is
+/// cut off just past `max_bytes` for servers that do not send one.
+pub fn fetch(url: &str, max_bytes: Option<u64>) -> Result<Option<Vec<u8>>> {
+    let response = reqwest::blocking::get(url)?.error_for_status()?;
+    if let (Some(max_bytes), Some(length)) = (max_bytes, response.content_length())
+        && length > max_bytes
+    {
+        return Ok(None);
+    }
+
+    let mut body = Vec::new();
+    let limit = max_bytes.map_or(u64::MAX, |max_bytes| max_bytes.saturating_add(1));
+    response.take(limit).read_to_end(&mut body)?;
+    if max_bytes.is_some_and(|max_bytes| body.len() as u64 > max_bytes) {
+        return Ok(None);
+    }
+    Ok(Some(body))
+}
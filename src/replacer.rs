@@ -1,16 +1,26 @@
-use crate::cli::Preset;
-use crate::config::{ConfigLoader, ReplaceConfig};
+use crate::cli::{ReplaceArgs, ReportFormat};
+use crate::config::{ConfigLoader, LineEnding, ReplaceConfig, WalkOptions};
 use crate::errors::Result;
+use crate::fingerprint::Fingerprinter;
 use crate::patterns::PatternManager;
+use crate::scanner::skip_symlink_loop;
+use crate::state_manager::{FileState, ReplaceState, StateManager};
 use ignore::WalkBuilder;
+use log::{debug, error, warn};
 use rayon::prelude::*;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::io::{IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use tempfile::NamedTempFile;
 
 /// Core engine for finding and replacing patterns in files.
@@ -20,24 +30,169 @@ use tempfile::NamedTempFile;
 pub struct Replacer {
     patterns: Vec<Regex>,
     replacements: Vec<Option<String>>,
+    /// The most changes each pattern may make per file, across all passes.
+    limits: Vec<Option<usize>>,
     blocks: Vec<BlockPattern>,
+    line_filter: Option<Regex>,
+    max_passes: usize,
+    /// A shell command that produces the replacement for each match, overriding
+    /// `replacements`.
+    replace_command: Option<String>,
+    /// If set, each pattern only replaces its Nth (1-based) match in each file.
+    nth: Option<usize>,
+    /// If set, every line ending is converted to this style after the patterns.
+    line_endings: Option<LineEnding>,
 }
 
+/// The pass limit for `--repeat` when `--max-passes` is not given.
+pub const DEFAULT_MAX_PASSES: usize = 10;
+
 /// A compiled regex pattern for an ignorable block of text.
 pub struct BlockPattern {
     regex: Regex,
-    #[allow(dead_code)]
     start: String,
-    #[allow(dead_code)]
     end: String,
 }
 
+impl BlockPattern {
+    /// The label used for this block in per-pattern change counts.
+    fn label(&self) -> String {
+        format!("{} ... {}", self.start, self.end)
+    }
+}
+
+/// A directory tree that backups are written to instead of sibling `.bak` files
+/// (`--backup-dir`). Each backup mirrors the path of its file relative to the processed
+/// directory, so the backup of `src/foo.rs` is `<root>/src/foo.rs`.
+#[derive(Debug, Clone)]
+pub struct BackupDir {
+    root: PathBuf,
+    base: PathBuf,
+    /// Where `root` lies under `base`, if it does, so the walks can skip it.
+    inside_base: Option<PathBuf>,
+}
+
+impl BackupDir {
+    /// Creates a backup tree at `root` for the files under `base`.
+    pub fn new(root: PathBuf, base: PathBuf) -> Self {
+        let inside_base = std::path::absolute(&root)
+            .ok()
+            .zip(std::path::absolute(&base).ok())
+            .and_then(|(root, base)| root.strip_prefix(base).ok().map(Path::to_path_buf));
+        Self { root, base, inside_base }
+    }
+
+    /// Returns where the backup of `path` goes. A path outside `base` is mirrored in full.
+    pub fn backup_path(&self, path: &Path) -> PathBuf {
+        let relative = path.strip_prefix(&self.base).unwrap_or(path);
+        let mirrored: PathBuf = relative
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        self.root.join(mirrored)
+    }
+
+    /// Returns the file that `backup`, a file in the backup tree, was made from.
+    fn original_path(&self, backup: &Path) -> PathBuf {
+        self.base.join(backup.strip_prefix(&self.root).unwrap_or(backup))
+    }
+
+    /// Whether `path`, found by walking `base`, lies in the backup tree.
+    fn contains(&self, path: &Path) -> bool {
+        self.inside_base.as_deref().is_some_and(|inside| {
+            path.strip_prefix(&self.base).is_ok_and(|relative| relative.starts_with(inside))
+        })
+    }
+
+    /// Lists the backup files in the tree, which may not exist yet.
+    fn backups(&self) -> Result<Vec<PathBuf>> {
+        if !self.root.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut backups = Vec::new();
+        for entry in backup_walker(&self.root).build() {
+            let entry = entry?;
+            if entry.file_type().is_some_and(|t| t.is_file()) {
+                backups.push(entry.into_path());
+            }
+        }
+        Ok(backups)
+    }
+}
+
+/// Walks `dir` for backup files. `replace --hidden` and `--no-ignore` back up hidden and
+/// ignored files like any other, so their backups are not skipped either; `.git` never
+/// holds any.
+fn backup_walker(dir: &Path) -> WalkBuilder {
+    let walk = WalkOptions { hidden: true, no_ignore: true, follow_symlinks: false };
+    let mut walker = ConfigLoader::walk_builder(dir, walk);
+    walker.filter_entry(|entry| entry.file_name() != ".git");
+    walker
+}
+
+/// The timestamp format of `--timestamped-backups`, e.g. `2024-06-01T12-00-00-250000`
+/// in UTC. Microseconds keep two runs in the same second from sharing a backup name,
+/// and there are no colons, which Windows does not allow in file names.
+pub const BACKUP_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S-%6f";
+
+/// Splits a `.bak` file path into the path of the file it backs up and, for a
+/// timestamped backup (`foo.rs.2024-06-01T12-00-00-250000.bak`), its timestamp.
+fn split_backup_name(backup: &Path) -> (PathBuf, Option<SystemTime>) {
+    let original = backup.with_extension("");
+    let timestamp = original
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| chrono::NaiveDateTime::parse_from_str(ext, BACKUP_TIMESTAMP_FORMAT).ok());
+    match timestamp {
+        Some(timestamp) => (original.with_extension(""), Some(timestamp.and_utc().into())),
+        None => (original, None),
+    }
+}
+
+/// Removes `dir` and the directories under it if they hold no files.
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_empty_dirs(&entry.path())?;
+        }
+    }
+    if fs::read_dir(dir)?.next().is_none() {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
 /// Options for processing a file.
 pub struct ProcessOptions {
-    /// If `true`, a `.bak` file will be created before modifying a file.
+    /// If `true`, a backup will be created before modifying a file: a sibling `.bak`
+    /// file, or a copy in `backup_dir`.
     pub create_backup: bool,
+    /// The tree to write backups to instead of sibling `.bak` files.
+    pub backup_dir: Option<BackupDir>,
+    /// If set, `.bak` files are named with this timestamp (`foo.rs.<timestamp>.bak`), so
+    /// they do not overwrite the backups of earlier runs.
+    pub backup_timestamp: Option<String>,
     /// If `true`, changes will be calculated but not written to disk.
     pub dry_run: bool,
+    /// If `true`, files with more than one hardlink are rewritten in place so every
+    /// link sees the new content. This gives up the atomic temp-file-and-rename write:
+    /// an interruption mid-write can leave the file truncated. When `false`, such
+    /// files are still written atomically, which detaches this path from its other
+    /// links, and a warning is printed.
+    pub inplace_hardlinks: bool,
+    /// If `true`, the line edits that turn the old content into the new are returned
+    /// in `ProcessResult::edits`.
+    pub collect_edits: bool,
+    /// If `true`, the changed line ranges and their new lines are returned in
+    /// `ProcessResult::hunks`.
+    pub collect_hunks: bool,
+    /// If `true`, a unified diff of each change is printed to stdout before the file is
+    /// written (or instead, in a dry run), colored when stdout is a terminal.
+    pub print_diff: bool,
+    /// If `true` (unix only), atomically rewritten files get their original owner and
+    /// group back. This usually requires root; failures are reported as warnings.
+    pub preserve_owner: bool,
 }
 
 /// The result of processing a single file.
@@ -46,6 +201,68 @@ pub struct ProcessResult {
     pub changes: usize,
     /// `true` if the file was modified.
     pub modified: bool,
+    /// The number of changes made by each pattern or block, keyed by its source.
+    pub changes_by_pattern: BTreeMap<String, usize>,
+    /// The edits made to the file, if `ProcessOptions::collect_edits` was set.
+    pub edits: Vec<TextEdit>,
+    /// The changed line ranges, if `ProcessOptions::collect_hunks` was set.
+    pub hunks: Vec<LineHunk>,
+    /// The number of pattern passes that changed the content.
+    pub passes: usize,
+}
+
+/// A replacement of a text region, with 1-based lines and 1-based character columns.
+/// The end position is exclusive.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextEdit {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    /// The text that replaces the region.
+    pub inserted: String,
+}
+
+/// A range of whole lines (1-based, inclusive) and the lines that replace it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineHunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub lines: Vec<String>,
+}
+
+/// A file that was (or, in a dry run, would be) changed by `replace`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FileReport {
+    /// The file path, relative to the `--dir` being processed.
+    pub path: PathBuf,
+    /// The total number of changes in this file.
+    pub changes: usize,
+    /// The number of changes made by each pattern or block, keyed by its source.
+    pub patterns: BTreeMap<String, usize>,
+    /// The number of pattern passes that changed the file.
+    pub passes: usize,
+    /// The edits made to the file (only collected for `--format sarif`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub edits: Vec<TextEdit>,
+    /// The changed line ranges (only collected for `--format github-suggestions`).
+    #[serde(skip)]
+    pub hunks: Vec<LineHunk>,
+}
+
+/// The outcome of a `replace` run, printed as the summary or as JSON with `--format json`.
+#[derive(Debug, Serialize)]
+pub struct ReplaceReport {
+    /// `true` if no files were written.
+    pub dry_run: bool,
+    /// The number of files that were read and processed.
+    pub files_scanned: usize,
+    /// The number of files that were (or would be) changed.
+    pub files_changed: usize,
+    /// The total number of changes across all files.
+    pub total_changes: usize,
+    /// The changed files, sorted by path.
+    pub files: Vec<FileReport>,
 }
 
 /// Statistics from an `undo` operation.
@@ -65,13 +282,20 @@ impl Replacer {
         if replacements.len() < config.patterns.len() {
             replacements.resize(config.patterns.len(), None);
         }
+        let mut limits = config.limits.clone();
+        limits.resize(config.patterns.len(), None);
 
         // Compile regex patterns
         let regex_patterns: Vec<Regex> = config
             .patterns
             .iter()
-            .map(|p| Regex::new(p))
+            .map(|p| RegexBuilder::new(p).multi_line(config.multiline_anchors).build())
             .collect::<std::result::Result<Vec<_>, _>>()?;
+        for (pattern, replacement) in regex_patterns.iter().zip(&replacements) {
+            if let Some(replacement) = replacement {
+                check_group_refs(pattern, replacement)?;
+            }
+        }
 
         // Compile block patterns
         let blocks: Vec<BlockPattern> = config
@@ -94,30 +318,95 @@ impl Replacer {
         Ok(Self {
             patterns: regex_patterns,
             replacements,
+            limits,
             blocks,
+            line_filter: None,
+            max_passes: 1,
+            replace_command: None,
+            nth: None,
+            line_endings: config.line_endings,
         })
     }
 
+    /// Restricts patterns to lines that also match `filter` (e.g. `^import`).
+    ///
+    /// Filtered patterns are applied line by line, so they cannot match across lines.
+    /// Blocks are not affected by the filter.
+    pub fn with_line_filter(mut self, filter: Option<&str>) -> Result<Self> {
+        self.line_filter = filter.map(Regex::new).transpose()?;
+        Ok(self)
+    }
+
+    /// Re-applies all patterns until the content stops changing, for at most
+    /// `max_passes` passes (default: 1). Blocks are only removed once.
+    ///
+    /// Patterns with a `None` replacement delete a line on every pass their trigger
+    /// line still matches, so combine them with repeated passes carefully.
+    pub fn with_max_passes(mut self, max_passes: usize) -> Self {
+        self.max_passes = max_passes.max(1);
+        self
+    }
+
+    /// Replaces every match of every pattern with the output of `command`, which is
+    /// given the matched text on stdin. See `run_replace_command`.
+    pub fn with_replace_command(mut self, command: Option<&str>) -> Self {
+        self.replace_command = command.map(str::to_string);
+        self
+    }
+
+    /// Makes each pattern replace only its `nth` match (1-based) in each file, leaving
+    /// the others intact. Every pattern needs a replacement, since line deletion has no
+    /// single match to pick.
+    pub fn with_nth(mut self, nth: Option<usize>) -> Result<Self> {
+        if nth == Some(0) {
+            return Err("--nth counts from 1".into());
+        }
+        if nth.is_some() && self.replacements.iter().any(Option::is_none) {
+            return Err("--nth needs a replacement for every pattern".into());
+        }
+        self.nth = nth;
+        Ok(self)
+    }
+
     /// Processes a single file, applying all configured replacements.
     ///
     /// The process is as follows:
     /// 1. Ignorable blocks are removed from the content.
     /// 2. Each pattern is applied in order. If a pattern has a corresponding
-    ///    replacement string, a find-and-replace is performed. If the replacement
-    ///    is `None`, the line *after* a matching line is removed.
-    /// 3. If any changes were made and `dry_run` is false, the new content is
+    ///    replacement string, a find-and-replace is performed. The replacement may
+    ///    reference capture groups (`$1`, `${name}`), which allows rewriting only part
+    ///    of a match by re-emitting the groups that should be kept. If the replacement
+    ///    is `None`, the line *after* a matching line is removed. With a line filter,
+    ///    patterns only apply to lines that match the filter. With more than one pass
+    ///    allowed, this step repeats until the content stops changing. A pattern with
+    ///    a limit stops once it has made that many changes to the file. In a file whose
+    ///    lines end in `\r\n`, newlines in the replacement text are written as `\r\n`.
+    /// 3. With `line_endings`, every line ending that differs from it is converted,
+    ///    each counting as one change.
+    /// 4. If any changes were made and `dry_run` is false, the new content is
     ///    written to the file atomically.
+    ///
+    /// Errors are reported as `Error::Processing`, naming `path`.
     pub fn process_file(&self, path: &Path, options: ProcessOptions) -> Result<ProcessResult> {
+        self.rewrite_file(path, options).map_err(|e| e.for_file(path))
+    }
+
+    /// The body of `process_file`, before the path is attached to its errors.
+    fn rewrite_file(&self, path: &Path, options: ProcessOptions) -> Result<ProcessResult> {
+        debug!("Processing {}", path.display());
+
         // Read file
         let content = fs::read_to_string(path)?;
         let mut new_content = Cow::Borrowed(content.as_str());
         let mut total_changes = 0;
+        let mut changes_by_pattern = BTreeMap::new();
 
         // Remove blocks first
         for block in &self.blocks {
             let matches = block.regex.find_iter(new_content.as_ref()).count();
             if matches > 0 {
                 total_changes += matches;
+                *changes_by_pattern.entry(block.label()).or_insert(0) += matches;
                 new_content = Cow::Owned(block.regex.replace_all(new_content.as_ref(), "").into_owned());
             }
         }
@@ -131,15 +420,209 @@ impl Replacer {
             new_content = Cow::Owned(clean_empty_lines(new_content.as_ref()));
         }
 
-        // Process patterns
+        // Process patterns, repeating passes until the content stops changing
+        let mut passes = 0;
+        let mut budgets = self.limits.clone();
+        let crlf = uses_crlf(&content);
+        while passes < self.max_passes {
+            let (next, pass_changes) = self.apply_patterns(
+                new_content.as_ref(),
+                content.ends_with('\n'),
+                &mut budgets,
+                crlf,
+            )?;
+            // A later pass that rewrites the text to itself has also reached the fixed point
+            let Some(next) = next.filter(|next| passes == 0 || next != new_content.as_ref()) else {
+                break;
+            };
+            passes += 1;
+            for (pattern, count) in pass_changes {
+                total_changes += count;
+                *changes_by_pattern.entry(pattern).or_insert(0) += count;
+            }
+            new_content = Cow::Owned(next);
+        }
+
+        if self.max_passes > 1
+            && passes == self.max_passes
+            && self
+                .apply_patterns(new_content.as_ref(), content.ends_with('\n'), &mut budgets, crlf)?
+                .0
+                .is_some_and(|next| next != new_content.as_ref())
+        {
+            warn!(
+                "{} was still changing after {} passes",
+                path.display(),
+                self.max_passes
+            );
+        }
+
+        if let Some(line_endings) = self.line_endings
+            && let Some((converted, count)) =
+                convert_line_endings(new_content.as_ref(), line_endings)
+        {
+            total_changes += count;
+            let label = match line_endings {
+                LineEnding::Lf => "line endings -> lf",
+                LineEnding::Crlf => "line endings -> crlf",
+            };
+            changes_by_pattern.insert(label.to_string(), count);
+            new_content = Cow::Owned(converted);
+        }
+
+        if options.print_diff && total_changes > 0 {
+            let label = path.display().to_string();
+            let color = std::io::stdout().is_terminal();
+            print!("{}", unified_diff(&label, &content, new_content.as_ref(), color));
+        }
+
+        // Write if changed
+        if total_changes > 0 && !options.dry_run {
+            if options.create_backup {
+                match &options.backup_dir {
+                    Some(backup_dir) => {
+                        let backup_path = backup_dir.backup_path(path);
+                        if let Some(parent) = backup_path.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        fs::copy(path, &backup_path)?;
+                    }
+                    None => {
+                        let backup_path = match &options.backup_timestamp {
+                            Some(timestamp) => format!("{}.{timestamp}.bak", path.display()),
+                            None => format!("{}.bak", path.display()),
+                        };
+                        fs::copy(path, backup_path)?;
+                    }
+                }
+            }
+
+            let hardlinked = has_multiple_links(path)?;
+            if hardlinked && options.inplace_hardlinks {
+                // Write through the existing inode to keep the hardlinks intact
+                fs::write(path, new_content.as_ref().as_bytes())?;
+            } else if let Some(parent) = path.parent() {
+                if hardlinked {
+                    warn!(
+                        "{} has multiple hardlinks; rewriting it breaks the link (use --inplace-hardlinks to preserve it)",
+                        path.display()
+                    );
+                }
+
+                // Write atomically using tempfile
+                let mut temp_file = NamedTempFile::new_in(parent)?;
+                temp_file.write_all(new_content.as_ref().as_bytes())?;
+
+                // Preserve file permissions
+                let metadata = fs::metadata(path)?;
+                fs::set_permissions(temp_file.path(), metadata.permissions())?;
+
+                temp_file.persist(path)?;
+
+                // The new inode belongs to the current user unless ownership is restored
+                if options.preserve_owner {
+                    restore_owner(path, &metadata);
+                }
+            } else {
+                return Err(format!("Could not get parent directory for {}", path.display()).into());
+            }
+        }
+
+        let edits = if options.collect_edits && total_changes > 0 {
+            line_edits(&content, new_content.as_ref())
+        } else {
+            Vec::new()
+        };
+        let hunks = if options.collect_hunks && total_changes > 0 {
+            line_hunks(&content, new_content.as_ref())
+        } else {
+            Vec::new()
+        };
+
+        Ok(ProcessResult {
+            changes: total_changes,
+            modified: total_changes > 0,
+            changes_by_pattern,
+            edits,
+            hunks,
+            passes,
+        })
+    }
+
+    /// Applies every pattern once to `content`. Returns the new content, or `None` if
+    /// no pattern matched, along with the number of changes made by each pattern.
+    /// Each pattern's changes are taken out of its entry in `budgets`, if it has one.
+    /// With `crlf`, newlines in the replacements are written as `\r\n`.
+    /// Fails only if the replace command fails.
+    fn apply_patterns(
+        &self,
+        content: &str,
+        keep_trailing_newline: bool,
+        budgets: &mut [Option<usize>],
+        crlf: bool,
+    ) -> Result<(Option<String>, BTreeMap<String, usize>)> {
+        let mut content = Cow::Borrowed(content);
+        let mut changes = BTreeMap::new();
+
         for (i, pattern) in self.patterns.iter().enumerate() {
-            if let Some(ref replacement) = self.replacements[i] {
+            let limit = budgets[i];
+            if limit == Some(0) {
+                continue;
+            }
+            let mut record = |matches: usize| {
+                *changes.entry(pattern.to_string()).or_insert(0) += matches;
+                if let Some(budget) = &mut budgets[i] {
+                    *budget -= matches;
+                }
+            };
+
+            if let Some(command) = &self.replace_command {
+                let (replaced, matches) = replace_with_command(
+                    content.as_ref(),
+                    pattern,
+                    command,
+                    self.line_filter.as_ref(),
+                    limit,
+                    crlf,
+                )?;
+                if matches > 0 {
+                    record(matches);
+                    content = Cow::Owned(replaced);
+                }
+            } else if let Some(ref replacement) = self.replacements[i] {
+                let replacement = if crlf { with_crlf(replacement) } else { replacement.into() };
+                let replacement = replacement.as_ref();
+                if let Some(nth) = self.nth {
+                    if let Some(replaced) = replace_nth(content.as_ref(), pattern, replacement, nth)
+                    {
+                        record(1);
+                        content = Cow::Owned(replaced);
+                    }
+                    continue;
+                }
                 // Replace pattern
-                let matches = pattern.find_iter(new_content.as_ref()).count();
+                if let Some(filter) = &self.line_filter {
+                    let (replaced, matches) = replace_filtered_lines(
+                        content.as_ref(),
+                        pattern,
+                        replacement,
+                        filter,
+                        limit,
+                    );
+                    if matches > 0 {
+                        record(matches);
+                        content = Cow::Owned(replaced);
+                    }
+                    continue;
+                }
+
+                let found = pattern.find_iter(content.as_ref()).count();
+                let matches = limit.map_or(found, |limit| found.min(limit));
                 if matches > 0 {
-                    total_changes += matches;
-                    new_content =
-                        Cow::Owned(pattern.replace_all(new_content.as_ref(), replacement).into_owned());
+                    record(matches);
+                    content = Cow::Owned(
+                        pattern.replacen(content.as_ref(), matches, replacement).into_owned(),
+                    );
                 }
             } else {
                 // Delete lines after pattern
@@ -147,7 +630,7 @@ impl Replacer {
                 let mut skip_next = false;
                 let mut removed = 0;
 
-                for line in new_content.lines() {
+                for line in content.lines() {
                     if skip_next {
                         removed += 1;
                         skip_next = false;
@@ -155,75 +638,108 @@ impl Replacer {
                     }
                     new_lines.push(line);
                     // Check if line matches the pattern (convert pattern to string for simple matching)
-                    if pattern.is_match(line) {
+                    if pattern.is_match(line)
+                        && self.line_filter.as_ref().is_none_or(|f| f.is_match(line))
+                        && limit.is_none_or(|limit| removed < limit)
+                    {
                         skip_next = true;
                     }
                 }
 
                 if removed > 0 {
-                    total_changes += removed;
+                    record(removed);
                     let mut joined = new_lines.join("\n");
-                    if !joined.ends_with('\n') && content.ends_with('\n') {
+                    if !joined.ends_with('\n') && keep_trailing_newline {
                         joined.push('\n');
                     }
-                    new_content = Cow::Owned(joined);
+                    content = Cow::Owned(joined);
                 }
             }
         }
 
-        // Write if changed
-        if total_changes > 0 && !options.dry_run {
-            if options.create_backup {
-                let backup_path = format!("{}.bak", path.display());
-                fs::copy(path, &backup_path)?;
-            }
-
-            // Write atomically using tempfile
-            if let Some(parent) = path.parent() {
-                let mut temp_file = NamedTempFile::new_in(parent)?;
-                temp_file.write_all(new_content.as_ref().as_bytes())?;
-
-                // Preserve file permissions
-                let perms = fs::metadata(path)?.permissions();
-                fs::set_permissions(temp_file.path(), perms)?;
-
-                temp_file.persist(path)?;
-            } else {
-                return Err(format!("Could not get parent directory for {}", path.display()).into());
-            }
-        }
-
-        Ok(ProcessResult {
-            changes: total_changes,
-            modified: total_changes > 0,
+        Ok(match content {
+            Cow::Owned(content) => (Some(content), changes),
+            Cow::Borrowed(_) => (None, changes),
         })
     }
 
-    /// Scans a directory for `.bak` files and restores them.
+    /// Scans a directory for `.bak` files, and the backup tree if there is one, and
+    /// restores them.
+    ///
+    /// A file with several timestamped backups gets its most recent one back, and the
+    /// older ones are left for the next `undo`. With `all`, it gets its oldest one back,
+    /// undoing every run at once, and every backup counts as restored.
     ///
     /// # Arguments
     ///
     /// * `dir` - The directory to scan for backup files.
-    /// * `keep_backups` - If `false`, the `.bak` files will be deleted after being restored.
-    pub fn undo(dir: &Path, keep_backups: bool) -> Result<UndoStats> {
+    /// * `backup_dir` - The `--backup-dir` given to `replace`, if any.
+    /// * `keep_backups` - If `false`, the backup files will be deleted after being restored.
+    /// * `all` - Whether to roll back to the oldest timestamped backup.
+    pub fn undo(
+        dir: &Path,
+        backup_dir: Option<&Path>,
+        keep_backups: bool,
+        all: bool,
+    ) -> Result<UndoStats> {
         let mut found = 0;
         let mut restored = 0;
+        let backup_dir = backup_dir.map(|root| BackupDir::new(root.into(), dir.into()));
 
-        for entry in WalkBuilder::new(dir).build() {
+        if let Some(backup_dir) = &backup_dir {
+            for backup in backup_dir.backups()? {
+                found += 1;
+                let original_path = backup_dir.original_path(&backup);
+                if let Some(parent) = original_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&backup, &original_path)?;
+                if !keep_backups {
+                    fs::remove_file(&backup)?;
+                }
+                restored += 1;
+                println!("Restored {}", original_path.display());
+            }
+            if !keep_backups && backup_dir.root.is_dir() {
+                remove_empty_dirs(&backup_dir.root)?;
+            }
+        }
+
+        let mut by_original: BTreeMap<PathBuf, Vec<(SystemTime, PathBuf)>> = BTreeMap::new();
+        for entry in backup_walker(dir).build() {
             let entry = entry?;
             let path = entry.path();
+            if backup_dir.as_ref().is_some_and(|backup_dir| backup_dir.contains(path)) {
+                continue;
+            }
             if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("bak") {
                 found += 1;
-                let original_path = path.with_extension("");
-                if path.exists() {
-                    fs::copy(path, &original_path)?;
-                    if !keep_backups {
-                        fs::remove_file(path)?;
-                    }
-                    restored += 1;
-                    println!("Restored {}", original_path.display());
+                // A plain `.bak` was made when it was last modified
+                let (original_path, timestamp) = split_backup_name(path);
+                let made = match timestamp {
+                    Some(timestamp) => timestamp,
+                    None => entry.metadata()?.modified()?,
+                };
+                by_original.entry(original_path).or_default().push((made, path.into()));
+            }
+        }
+
+        for (original_path, mut backups) in by_original {
+            backups.sort();
+            let (restore, consumed) = if all {
+                (&backups[0].1, &backups[..])
+            } else {
+                let newest = backups.len() - 1;
+                (&backups[newest].1, &backups[newest..])
+            };
+            fs::copy(restore, &original_path)?;
+            if !keep_backups {
+                for (_, backup) in consumed {
+                    fs::remove_file(backup)?;
                 }
             }
+            restored += 1;
+            println!("Restored {}", original_path.display());
         }
 
         Ok(UndoStats { found, restored })
@@ -236,35 +752,164 @@ impl Replacer {
 /// 1. It loads the configuration from a preset, a file, or command-line arguments.
 /// 2. It walks the target directory to find all files to be processed.
 /// 3. It uses a Rayon thread pool to process the files in parallel.
-/// 4. It collects and prints summary statistics.
-pub fn run_replace(
-    preset: Option<Preset>,
-    config_file: Option<PathBuf>,
-    pattern: Option<String>,
-    replacement: Option<String>,
-    dir: PathBuf,
-    extensions: Vec<String>,
-    exclude: Vec<String>,
-    no_backup: bool,
-    dry_run: bool,
-    verbose: bool,
-    workers: Option<usize>,
-) -> Result<()> {
+/// 4. If an `--on-change` command is given, it runs it for each modified file.
+/// 5. It prints summary statistics, or the full report as JSON with `--format json`.
+pub fn run_replace(args: ReplaceArgs) -> Result<()> {
+    let format = args.format;
+    let report = replace_with_report(args)?;
+
+    match format {
+        ReportFormat::Text => {
+            println!("\n{}", "-".repeat(50));
+            println!("Files scanned : {}", report.files_scanned);
+            println!("Files changed : {}", report.files_changed);
+            println!("Total edits   : {}", report.total_changes);
+        }
+        ReportFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        ReportFormat::Sarif => {
+            println!("{}", format_sarif_fixes(&report)?);
+        }
+        ReportFormat::GithubSuggestions => {
+            print!("{}", format_github_suggestions(&report));
+        }
+    }
+
+    Ok(())
+}
+
+/// Fails if `dir` is inside a git repository and `git status` reports changes under it,
+/// for `--require-clean-tree`. A directory git does not manage passes. If git cannot be
+/// run, or fails for any other reason, the tree cannot be checked, which is an error.
+fn ensure_clean_tree(dir: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["status", "--porcelain", "--", "."])
+        // Keeps git's messages in English, so "not a git repository" can be recognized
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|e| {
+            format!("--require-clean-tree: cannot run git: {e}; pass --force to skip the check")
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            debug!("--require-clean-tree: {} is not in a git repository", dir.display());
+            return Ok(());
+        }
+        return Err(format!(
+            "--require-clean-tree: git status failed in {}: {}; pass --force to skip the check",
+            dir.display(),
+            stderr.trim()
+        )
+        .into());
+    }
+
+    let changed = output.stdout.split(|&b| b == b'\n').filter(|line| !line.is_empty()).count();
+    if changed > 0 {
+        return Err(format!(
+            "{} has uncommitted changes ({} paths); commit or stash them, or pass --force",
+            dir.display(),
+            changed
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Runs the replacement described by `args` and collects a per-file report.
+///
+/// In a dry run the report lists the changes that would be made; nothing is written.
+fn replace_with_report(args: ReplaceArgs) -> Result<ReplaceReport> {
+    let ReplaceArgs {
+        preset,
+        config: config_file,
+        pattern,
+        replacement,
+        replacement_file,
+        replace_cmd,
+        wrap_before,
+        wrap_after,
+        multiline_anchors,
+        fixed_strings,
+        line_endings,
+        dir,
+        extensions,
+        exclude,
+        hidden,
+        no_ignore,
+        follow_symlinks,
+        no_backup,
+        backup_dir,
+        timestamped_backups,
+        dry_run,
+        diff,
+        verbose,
+        workers,
+        on_change,
+        inplace_hardlinks,
+        root,
+        format,
+        line_filter,
+        repeat,
+        max_passes,
+        preserve_owner,
+        incremental,
+        content_hash,
+        require_clean_tree,
+        force,
+        nth,
+    } = args;
+    let text_output = format == ReportFormat::Text;
+    if format == ReportFormat::GithubSuggestions && !dry_run {
+        return Err("--format github-suggestions requires --dry-run".into());
+    }
+
+    // Relative paths are resolved against --root instead of the current directory
+    let dir = ConfigLoader::resolve_path(root.as_deref(), &dir);
+    let backup_dir = backup_dir.map(|path| {
+        BackupDir::new(ConfigLoader::resolve_path(root.as_deref(), &path), dir.clone())
+    });
+    let config_file = config_file.map(|path| ConfigLoader::resolve_path(root.as_deref(), &path));
+    if require_clean_tree && !dry_run && !force {
+        ensure_clean_tree(&dir)?;
+    }
+
     // Load or create config
-    let config = if let Some(preset_type) = preset {
+    let mut config = if let Some(preset_type) = preset {
         // Use built-in preset
-        println!("Using preset: {preset_type:?}");
+        if text_output {
+            println!("Using preset: {preset_type:?}");
+        }
         PatternManager::load_preset(&preset_type)
     } else if let Some(cfg_path) = config_file {
         // Use config file
         let resolved_path = ConfigLoader::find_config(&cfg_path, &dir)?;
-        println!("Using config file: {}", resolved_path.display());
+        if text_output {
+            println!("Using config file: {}", resolved_path.display());
+        }
         ConfigLoader::load_replace_config(&resolved_path)?
     } else if let Some(pat) = pattern {
         // Use single pattern/replacement
+        let replacement = match replacement_file {
+            Some(path) => {
+                let path = ConfigLoader::resolve_path(root.as_deref(), &path);
+                let contents = fs::read_to_string(&path).map_err(|e| {
+                    format!("Failed to read replacement file {}: {}", path.display(), e)
+                })?;
+                Some(contents)
+            }
+            None if wrap_before.is_some() || wrap_after.is_some() => {
+                Some(wrap_replacement(wrap_before.as_deref(), wrap_after.as_deref()))
+            }
+            None => replacement,
+        };
         ReplaceConfig {
             patterns: vec![pat],
             replacements: vec![replacement],
+            limits: vec![],
             blocks: vec![],
             extensions: if extensions.is_empty() {
                 None
@@ -276,10 +921,20 @@ pub fn run_replace(
             } else {
                 Some(exclude.clone())
             },
+            ignore_patterns: vec![],
+            multiline_anchors: false,
+            line_endings: None,
         }
     } else {
         return Err("Specify --preset, --config, or --pattern".into());
     };
+    config.multiline_anchors |= multiline_anchors;
+    config.line_endings = line_endings.or(config.line_endings);
+    if fixed_strings {
+        for pattern in &mut config.patterns {
+            *pattern = regex::escape(pattern);
+        }
+    }
 
     // Normalize extensions
     let exts: Vec<String> = config
@@ -299,33 +954,76 @@ pub fn run_replace(
 
     // Get exclude directories from config or command line
     let exclude_dirs = config.exclude.clone().unwrap_or_else(|| exclude.clone());
+    let ignore_patterns = config.ignore_patterns.clone();
 
     // Create replacer
-    let replacer = Arc::new(Replacer::new(config)?);
+    let max_passes = max_passes.unwrap_or(if repeat { DEFAULT_MAX_PASSES } else { 1 });
+    let rules_hash = compute_rules_hash(
+        &config,
+        line_filter.as_deref(),
+        max_passes,
+        replace_cmd.as_deref(),
+        nth,
+    );
+    let replacer = Arc::new(
+        Replacer::new(config)?
+            .with_line_filter(line_filter.as_deref())?
+            .with_max_passes(max_passes)
+            .with_replace_command(replace_cmd.as_deref())
+            .with_nth(nth)?,
+    );
 
     // Collect all files
     let mut all_files = Vec::new();
-    let mut walker = WalkBuilder::new(&dir);
-    walker.standard_filters(true); // Respect .gitignore
+    let walk = WalkOptions { hidden, no_ignore, follow_symlinks };
+    let mut walker = ConfigLoader::walk_builder(&dir, walk);
+    ConfigLoader::apply_ignore_patterns(&mut walker, &dir, &ignore_patterns)?;
 
-    for entry in walker.build() {
+    for entry in walker.build().filter_map(skip_symlink_loop) {
         let entry = entry?;
         let path = entry.path();
 
-        // Check if path should be excluded
+        // Check if path should be excluded. `.git` never is rewritten, even with --hidden,
+        // and neither are the backups
         let should_exclude = exclude_dirs
             .iter()
-            .any(|ex| path.components().any(|c| c.as_os_str() == ex.as_str()));
+            .map(String::as_str)
+            .chain([".git"])
+            .any(|ex| path.components().any(|c| c.as_os_str() == ex))
+            || backup_dir.as_ref().is_some_and(|backup_dir| backup_dir.contains(path));
 
         if !should_exclude && path.is_file() && should_process_file(path, &exts) {
             all_files.push(path.to_path_buf());
         }
     }
 
+    // With --incremental, skip files that had no matches last time and haven't changed
+    let fingerprinter = Fingerprinter::new(content_hash);
+    let state_manager = if incremental { Some(StateManager::new(&dir)?) } else { None };
+    let mut clean_files: HashMap<PathBuf, FileState> = HashMap::new();
+    if let Some(manager) = &state_manager
+        && let Some(state) = manager.load_replace_state()?
+        && state.patterns_hash == rules_hash
+    {
+        let mut remaining = Vec::with_capacity(all_files.len());
+        for path in all_files {
+            match state.files.get(&path) {
+                Some(file_state) if !fingerprinter.has_file_changed(&path, file_state)? => {
+                    clean_files.insert(path, file_state.clone());
+                }
+                _ => remaining.push(path),
+            }
+        }
+        debug!("Skipping {} unchanged file(s) with no matches", clean_files.len());
+        all_files = remaining;
+    }
+    let unmatched_files = Mutex::new(Vec::new());
+
     // Stats
     let processed = AtomicUsize::new(0);
     let modified = AtomicUsize::new(0);
     let total_changes = AtomicUsize::new(0);
+    let file_reports = Mutex::new(Vec::new());
 
     // Process files in parallel
     let pool = rayon::ThreadPoolBuilder::new()
@@ -338,50 +1036,154 @@ pub fn run_replace(
 
     let options = ProcessOptions {
         create_backup: !no_backup,
+        backup_dir,
+        backup_timestamp: timestamped_backups
+            .then(|| chrono::Utc::now().format(BACKUP_TIMESTAMP_FORMAT).to_string()),
         dry_run,
+        inplace_hardlinks,
+        collect_edits: format == ReportFormat::Sarif,
+        collect_hunks: format == ReportFormat::GithubSuggestions,
+        print_diff: text_output && (dry_run || diff),
+        preserve_owner,
     };
 
-    let log_changes = verbose || dry_run;
+    let log_changes = text_output && (verbose > 0 || dry_run);
 
     pool.install(|| {
         all_files.par_iter().for_each(|path| {
             match replacer.process_file(path, options.clone()) {
                 Ok(result) => {
                     processed.fetch_add(1, Ordering::Relaxed);
+                    if !result.modified && state_manager.is_some() {
+                        unmatched_files.lock().unwrap().push(path.clone());
+                    }
                     if result.modified {
                         modified.fetch_add(1, Ordering::Relaxed);
                         total_changes.fetch_add(result.changes, Ordering::Relaxed);
                         if log_changes {
+                            let passes = if result.passes > 1 {
+                                format!(", {} passes", result.passes)
+                            } else {
+                                String::new()
+                            };
                             if dry_run {
                                 println!(
-                                    "DRY Modified {} ({} changes)",
+                                    "DRY Modified {} ({} changes{})",
                                     path.display(),
-                                    result.changes
+                                    result.changes,
+                                    passes
                                 );
                             } else {
-                                println!("Modified {} ({} changes)", path.display(), result.changes);
+                                println!(
+                                    "Modified {} ({} changes{})",
+                                    path.display(),
+                                    result.changes,
+                                    passes
+                                );
                             }
                         }
+                        if let Some(cmd) = on_change.as_deref().filter(|_| !dry_run)
+                            && let Err(e) = run_on_change(cmd, path)
+                        {
+                            warn!("on-change command failed for {}: {}", path.display(), e);
+                        }
+                        let report = FileReport {
+                            path: path.strip_prefix(&dir).unwrap_or(path).to_path_buf(),
+                            changes: result.changes,
+                            patterns: result.changes_by_pattern,
+                            passes: result.passes,
+                            edits: result.edits,
+                            hunks: result.hunks,
+                        };
+                        file_reports.lock().unwrap().push(report);
                     }
                 }
                 Err(e) => {
-                    eprintln!("Error processing file {}: {}", path.display(), e);
+                    error!("{e}");
                 }
             }
         });
     });
 
-    println!("\n{}", "-".repeat(50));
-    println!("Files scanned : {}", processed.load(Ordering::Relaxed));
-    println!("Files changed : {}", modified.load(Ordering::Relaxed));
-    println!("Total edits   : {}", total_changes.load(Ordering::Relaxed));
+    let mut files = file_reports.into_inner().unwrap();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
 
-    Ok(())
+    if let Some(manager) = state_manager {
+        let now = SystemTime::now();
+        for path in unmatched_files.into_inner().unwrap() {
+            let fingerprint = fingerprinter.fingerprint_file(&path)?;
+            let file_state = FileState {
+                path: path.clone(),
+                modified: fingerprint.modified,
+                size: fingerprint.size,
+                hash: fingerprint.hash,
+                last_scanned: now,
+            };
+            clean_files.insert(path, file_state);
+        }
+        manager.save_replace_state(&ReplaceState {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            patterns_hash: rules_hash,
+            files: clean_files,
+        })?;
+    }
+
+    Ok(ReplaceReport {
+        dry_run,
+        files_scanned: processed.into_inner(),
+        files_changed: modified.into_inner(),
+        total_changes: total_changes.into_inner(),
+        files,
+    })
+}
+
+/// Hashes everything that decides how a file's content is rewritten, so an
+/// incremental run can tell when its cached results no longer apply.
+fn compute_rules_hash(
+    config: &ReplaceConfig,
+    line_filter: Option<&str>,
+    max_passes: usize,
+    replace_command: Option<&str>,
+    nth: Option<usize>,
+) -> String {
+    let mut hasher = Sha256::new();
+    for (i, pattern) in config.patterns.iter().enumerate() {
+        hasher.update(pattern.as_bytes());
+        hasher.update(b"\0");
+        match config.replacements.get(i).cloned().flatten() {
+            Some(replacement) => hasher.update(format!("={replacement}").as_bytes()),
+            None => hasher.update(b"-"),
+        }
+        if let Some(limit) = config.limits.get(i).copied().flatten() {
+            hasher.update(format!("#{limit}").as_bytes());
+        }
+        hasher.update(b"\0");
+    }
+    for block in &config.blocks {
+        hasher.update(format!("{}\0{}\0", block.start, block.end).as_bytes());
+    }
+    hasher.update(format!("{}\0{}", line_filter.unwrap_or_default(), max_passes).as_bytes());
+    hasher.update([config.multiline_anchors as u8]);
+    if let Some(line_endings) = config.line_endings {
+        hasher.update(format!("\0eol={line_endings:?}").as_bytes());
+    }
+    if let Some(command) = replace_command {
+        hasher.update(format!("\0|{command}").as_bytes());
+    }
+    if let Some(nth) = nth {
+        hasher.update(format!("\0@{nth}").as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
 }
 
 /// The main entry point for the `undo` command.
-pub fn run_undo(dir: PathBuf, keep_backups: bool) -> Result<()> {
-    let stats = Replacer::undo(&dir, keep_backups)?;
+pub fn run_undo(
+    dir: PathBuf,
+    backup_dir: Option<PathBuf>,
+    keep_backups: bool,
+    all: bool,
+) -> Result<()> {
+    let stats = Replacer::undo(&dir, backup_dir.as_deref(), keep_backups, all)?;
     println!(
         "\nBackups found: {}, restored: {}",
         stats.found, stats.restored
@@ -390,38 +1192,56 @@ pub fn run_undo(dir: PathBuf, keep_backups: bool) -> Result<()> {
 }
 
 /// The main entry point for the `clean-backups` command.
-pub fn run_clean_backups(dir: PathBuf, dry_run: bool) -> Result<()> {
+pub fn run_clean_backups(dir: PathBuf, backup_dir: Option<PathBuf>, dry_run: bool) -> Result<()> {
     let mut found = 0;
     let mut removed = 0;
     let mut total_size = 0u64;
 
     println!("Searching for backup files in {}...\n", dir.display());
 
-    for entry in WalkBuilder::new(&dir).build() {
+    let backup_dir = backup_dir.map(|root| BackupDir::new(root, dir.clone()));
+    let mut backups = match &backup_dir {
+        Some(backup_dir) => backup_dir.backups()?,
+        None => Vec::new(),
+    };
+    for entry in backup_walker(&dir).build() {
         let entry = entry?;
         let path = entry.path();
+        if backup_dir.as_ref().is_some_and(|backup_dir| backup_dir.contains(path)) {
+            continue;
+        }
         if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("bak") {
-            found += 1;
+            backups.push(entry.into_path());
+        }
+    }
 
-            if let Ok(metadata) = path.metadata() {
-                total_size += metadata.len();
-            }
+    for path in &backups {
+        found += 1;
 
-            if dry_run {
-                println!("Would remove: {}", path.display());
-            } else {
-                match fs::remove_file(path) {
-                    Ok(_) => {
-                        removed += 1;
-                        println!("Removed: {}", path.display());
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to remove {}: {}", path.display(), e);
-                    }
+        if let Ok(metadata) = path.metadata() {
+            total_size += metadata.len();
+        }
+
+        if dry_run {
+            println!("Would remove: {}", path.display());
+        } else {
+            match fs::remove_file(path) {
+                Ok(_) => {
+                    removed += 1;
+                    println!("Removed: {}", path.display());
+                }
+                Err(e) => {
+                    error!("Failed to remove {}: {}", path.display(), e);
                 }
             }
         }
     }
+    if let Some(backup_dir) = &backup_dir
+        && !dry_run
+        && backup_dir.root.is_dir()
+    {
+        remove_empty_dirs(&backup_dir.root)?;
+    }
 
     println!("\n{}", "-".repeat(50));
     if dry_run {
@@ -441,28 +1261,693 @@ impl Clone for ProcessOptions {
     fn clone(&self) -> Self {
         Self {
             create_backup: self.create_backup,
+            backup_dir: self.backup_dir.clone(),
+            backup_timestamp: self.backup_timestamp.clone(),
             dry_run: self.dry_run,
+            inplace_hardlinks: self.inplace_hardlinks,
+            collect_edits: self.collect_edits,
+            collect_hunks: self.collect_hunks,
+            print_diff: self.print_diff,
+            preserve_owner: self.preserve_owner,
         }
     }
 }
 
-/// Determines if a file should be processed based on its extension.
-fn should_process_file(path: &Path, extensions: &[String]) -> bool {
-    if extensions.is_empty() {
-        return true;
+/// Formats a replace report as SARIF, with one result per changed file whose `fixes`
+/// carry the edits, so review tools can show each replacement inline.
+fn format_sarif_fixes(report: &ReplaceReport) -> Result<String> {
+    let results: Vec<serde_json::Value> = report
+        .files
+        .iter()
+        .map(|file| {
+            let uri = file.path.display().to_string().replace('\\', "/");
+            let patterns: Vec<String> = file
+                .patterns
+                .iter()
+                .map(|(pattern, count)| format!("{pattern} ({count})"))
+                .collect();
+            let replacements: Vec<serde_json::Value> = file
+                .edits
+                .iter()
+                .map(|edit| {
+                    json!({
+                        "deletedRegion": {
+                            "startLine": edit.start_line,
+                            "startColumn": edit.start_column,
+                            "endLine": edit.end_line,
+                            "endColumn": edit.end_column,
+                        },
+                        "insertedContent": { "text": edit.inserted },
+                    })
+                })
+                .collect();
+            let first_line = file.edits.first().map_or(1, |edit| edit.start_line);
+
+            json!({
+                "ruleId": "replace",
+                "level": "note",
+                "message": {
+                    "text": format!("{} changes: {}", file.changes, patterns.join(", ")),
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                        "region": { "startLine": first_line },
+                    },
+                }],
+                "fixes": [{
+                    "description": { "text": format!("Apply {} replacements", file.changes) },
+                    "artifactChanges": [{
+                        "artifactLocation": { "uri": uri },
+                        "replacements": replacements,
+                    }],
+                }],
+            })
+        })
+        .collect();
+
+    let sarif = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "oober",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [{
+                        "id": "replace",
+                        "shortDescription": { "text": "Configured replacement" },
+                    }],
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+
+    Ok(serde_json::to_string_pretty(&sarif)?)
+}
+
+/// Formats a replace report as GitHub review suggestions: for each changed region, the
+/// file and line range followed by a ```suggestion block holding the new lines.
+fn format_github_suggestions(report: &ReplaceReport) -> String {
+    let mut blocks = Vec::new();
+    for file in &report.files {
+        let path = file.path.display().to_string().replace('\\', "/");
+        for hunk in &file.hunks {
+            let location = if hunk.start_line == hunk.end_line {
+                format!("{path}:{}", hunk.start_line)
+            } else {
+                format!("{path}:{}-{}", hunk.start_line, hunk.end_line)
+            };
+            // The fence must be longer than any backtick run in the suggested lines
+            let fence = if hunk.lines.iter().any(|line| line.contains("```")) {
+                "````"
+            } else {
+                "```"
+            };
+            let mut block = format!("{location}\n{fence}suggestion\n");
+            for line in &hunk.lines {
+                block.push_str(line);
+                block.push('\n');
+            }
+            block.push_str(fence);
+            block.push('\n');
+            blocks.push(block);
+        }
     }
+    blocks.join("\n")
+}
 
-    path.extension()
-        .and_then(|os| os.to_str())
-        .map(|s| extensions.contains(&s.to_lowercase()))
-        .unwrap_or(false)
+/// Returns the number of lines `old` and `new` have in common at the start and at the
+/// end. The two counts never overlap.
+fn common_lines(old: &[&str], new: &[&str]) -> (usize, usize) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, suffix)
 }
 
-/// Cleans up excessive empty lines from a string.
-///
-/// This is a heuristic to improve formatting after blocks of code have been removed.
-/// It collapses multiple empty lines and removes leading/trailing empty lines.
-fn clean_empty_lines(content: &str) -> String {
+/// Lines of unchanged context shown around each change in a unified diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// Above this many pairs of changed old and new lines, a unified diff shows the changed
+/// region as one removed and one added block instead of lining the lines up, which
+/// would take too long.
+const DIFF_MAX_PAIRS: usize = 4_000_000;
+
+/// How a line takes part in a diff.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiffOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Lines `old` up with `new` along a longest common subsequence, returning what happens
+/// to each line in order. Removed lines come before the lines added in their place.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let (prefix, suffix) = common_lines(old, new);
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    let (n, m) = (old_mid.len(), new_mid.len());
+
+    let mut ops = vec![DiffOp::Equal; prefix];
+    if n.saturating_mul(m) > DIFF_MAX_PAIRS {
+        ops.extend(std::iter::repeat_n(DiffOp::Delete, n));
+        ops.extend(std::iter::repeat_n(DiffOp::Insert, m));
+    } else {
+        // lcs[i * width + j] is the length of the LCS of old_mid[i..] and new_mid[j..]
+        let width = m + 1;
+        let mut lcs = vec![0u32; (n + 1) * width];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                ops.push(DiffOp::Equal);
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1]) {
+                ops.push(DiffOp::Delete);
+                i += 1;
+            } else {
+                ops.push(DiffOp::Insert);
+                j += 1;
+            }
+        }
+    }
+    ops.extend(std::iter::repeat_n(DiffOp::Equal, suffix));
+    ops
+}
+
+/// Formats the change from `old` to `new` as a unified diff, with `label` as the file
+/// name on both sides, or returns an empty string if they are equal. With `color`,
+/// removed lines are red, added lines green and hunk headers cyan.
+fn unified_diff(label: &str, old: &str, new: &str, color: bool) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff_lines(&old_lines, &new_lines);
+    let changes: Vec<usize> = (0..ops.len()).filter(|&i| ops[i] != DiffOp::Equal).collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // The old and new line indexes before each op, and after the last
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_at, mut new_at) = (0, 0);
+    for op in &ops {
+        positions.push((old_at, new_at));
+        old_at += usize::from(*op != DiffOp::Insert);
+        new_at += usize::from(*op != DiffOp::Delete);
+    }
+    positions.push((old_at, new_at));
+
+    let paint = |code: &str, text: String| {
+        if color { format!("\x1b[{code}m{text}\x1b[0m") } else { text }
+    };
+    let range = |start: usize, count: usize| match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{count}", start + 1),
+    };
+
+    let mut out = format!("--- {label}\n+++ {label}\n");
+    let mut next = 0;
+    while next < changes.len() {
+        let start = changes[next].saturating_sub(DIFF_CONTEXT);
+        let mut end = changes[next] + 1;
+        next += 1;
+        // Changes with little enough context between them share a hunk
+        while next < changes.len() && changes[next] - end <= 2 * DIFF_CONTEXT {
+            end = changes[next] + 1;
+            next += 1;
+        }
+        let end = (end + DIFF_CONTEXT).min(ops.len());
+
+        let ((old_start, new_start), (old_end, new_end)) = (positions[start], positions[end]);
+        let header = format!(
+            "@@ -{} +{} @@",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        );
+        out.push_str(&paint("36", header));
+        out.push('\n');
+        for (op, &(old_at, new_at)) in ops[start..end].iter().zip(&positions[start..end]) {
+            let (sign, line, code) = match op {
+                DiffOp::Equal => (' ', old_lines[old_at], None),
+                DiffOp::Delete => ('-', old_lines[old_at], Some("31")),
+                DiffOp::Insert => ('+', new_lines[new_at], Some("32")),
+            };
+            let text = format!("{sign}{}", line.strip_suffix('\n').unwrap_or(line));
+            out.push_str(&match code {
+                Some(code) => paint(code, text),
+                None => text,
+            });
+            out.push('\n');
+            if !line.ends_with('\n') {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// Computes the line ranges of `old` that change, and the lines of `new` that replace
+/// them.
+///
+/// Changed lines that line up one-to-one are grouped into runs of consecutive lines;
+/// otherwise the whole changed range is one hunk. Inserted lines have no range of their
+/// own, so they are attached to the line above them (or below, at the top of the file).
+fn line_hunks(old: &str, new: &str) -> Vec<LineHunk> {
+    let old_lines: Vec<&str> = old.split('\n').collect();
+    let new_lines: Vec<&str> = new.split('\n').collect();
+    let (prefix, suffix) = common_lines(&old_lines, &new_lines);
+    let old_mid = &old_lines[prefix..old_lines.len() - suffix];
+    let new_mid = &new_lines[prefix..new_lines.len() - suffix];
+    let to_strings = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+
+    if old_mid.len() == new_mid.len() {
+        let mut hunks: Vec<LineHunk> = Vec::new();
+        for (i, (a, b)) in old_mid.iter().zip(new_mid).enumerate() {
+            if a == b {
+                continue;
+            }
+            let line = prefix + i + 1;
+            match hunks.last_mut() {
+                Some(hunk) if hunk.end_line + 1 == line => {
+                    hunk.end_line = line;
+                    hunk.lines.push(b.to_string());
+                }
+                _ => hunks.push(LineHunk {
+                    start_line: line,
+                    end_line: line,
+                    lines: vec![b.to_string()],
+                }),
+            }
+        }
+        return hunks;
+    }
+
+    // The empty string after a trailing newline is not a line of its own
+    let line_count = (old_lines.len() - usize::from(old.ends_with('\n'))).max(1);
+    let hunk = if !old_mid.is_empty() && prefix < line_count {
+        LineHunk {
+            start_line: prefix + 1,
+            end_line: (prefix + old_mid.len()).min(line_count),
+            lines: to_strings(new_mid),
+        }
+    } else if prefix > 0 {
+        let anchor = prefix.min(line_count);
+        let mut lines = vec![old_lines[anchor - 1].to_string()];
+        lines.extend(to_strings(new_mid));
+        LineHunk { start_line: anchor, end_line: anchor, lines }
+    } else {
+        let mut lines = to_strings(new_mid);
+        lines.push(old_lines[0].to_string());
+        LineHunk { start_line: 1, end_line: 1, lines }
+    };
+    vec![hunk]
+}
+
+/// Computes the line edits that turn `old` into `new`.
+///
+/// Unchanged lines at the start and end are skipped. If the remaining lines line up
+/// one-to-one, each changed line becomes its own edit; otherwise the whole changed range
+/// is replaced in one edit.
+fn line_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let old_lines: Vec<&str> = old.split('\n').collect();
+    let new_lines: Vec<&str> = new.split('\n').collect();
+    let width = |line: &str| line.chars().count() + 1;
+
+    let (prefix, suffix) = common_lines(&old_lines, &new_lines);
+    let old_mid = &old_lines[prefix..old_lines.len() - suffix];
+    let new_mid = &new_lines[prefix..new_lines.len() - suffix];
+
+    if old_mid.len() == new_mid.len() {
+        return old_mid
+            .iter()
+            .zip(new_mid)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, (old_line, new_line))| TextEdit {
+                start_line: prefix + i + 1,
+                start_column: 1,
+                end_line: prefix + i + 1,
+                end_column: width(old_line),
+                inserted: new_line.to_string(),
+            })
+            .collect();
+    }
+
+    let first = prefix + 1;
+    let last = prefix + old_mid.len();
+    let edit = match (old_mid.is_empty(), new_mid.is_empty()) {
+        // Lines were removed: also remove their line breaks
+        (false, true) if suffix > 0 => TextEdit {
+            start_line: first,
+            start_column: 1,
+            end_line: last + 1,
+            end_column: 1,
+            inserted: String::new(),
+        },
+        (false, true) => TextEdit {
+            start_line: prefix,
+            start_column: width(old_lines[prefix - 1]),
+            end_line: last,
+            end_column: width(old_mid[old_mid.len() - 1]),
+            inserted: String::new(),
+        },
+        // Lines were added between unchanged lines
+        (true, _) if suffix > 0 => TextEdit {
+            start_line: first,
+            start_column: 1,
+            end_line: first,
+            end_column: 1,
+            inserted: format!("{}\n", new_mid.join("\n")),
+        },
+        (true, _) => TextEdit {
+            start_line: prefix,
+            start_column: width(old_lines[prefix - 1]),
+            end_line: prefix,
+            end_column: width(old_lines[prefix - 1]),
+            inserted: format!("\n{}", new_mid.join("\n")),
+        },
+        (false, false) => TextEdit {
+            start_line: first,
+            start_column: 1,
+            end_line: last,
+            end_column: width(old_mid[old_mid.len() - 1]),
+            inserted: new_mid.join("\n"),
+        },
+    };
+
+    vec![edit]
+}
+
+/// Runs the `--on-change` command for a modified file.
+///
+/// Every `{}` in the command is replaced with the file path. On unix the path is
+/// passed to `sh` as a positional argument rather than spliced into the script,
+/// so paths containing spaces or quotes are handled safely.
+fn run_on_change(command: &str, path: &Path) -> Result<()> {
+    let status = on_change_command(command, path).status()?;
+    if !status.success() {
+        return Err(format!("`{command}` exited with {status}").into());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn on_change_command(command: &str, path: &Path) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command.replace("{}", "\"$1\""))
+        .arg("sh")
+        .arg(path);
+    cmd
+}
+
+#[cfg(not(unix))]
+fn on_change_command(command: &str, path: &Path) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C")
+        .arg(command.replace("{}", &format!("\"{}\"", path.display())));
+    cmd
+}
+
+/// Returns `true` if the file has more than one hardlink.
+#[cfg(unix)]
+fn has_multiple_links(path: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(fs::metadata(path)?.nlink() > 1)
+}
+
+#[cfg(not(unix))]
+fn has_multiple_links(_path: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+/// Gives `path` the owner and group recorded in `original`, for `--preserve-owner`.
+#[cfg(unix)]
+fn restore_owner(path: &Path, original: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    let (uid, gid) = (original.uid(), original.gid());
+    debug!("Restoring owner {}:{} of {}", uid, gid, path.display());
+    if let Err(e) = std::os::unix::fs::chown(path, Some(uid), Some(gid)) {
+        warn!(
+            "Could not restore owner {}:{} of {}: {}",
+            uid,
+            gid,
+            path.display(),
+            e
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn restore_owner(_path: &Path, _original: &fs::Metadata) {}
+
+/// Determines if a file should be processed based on its extension.
+fn should_process_file(path: &Path, extensions: &[String]) -> bool {
+    if extensions.is_empty() {
+        return true;
+    }
+
+    path.extension()
+        .and_then(|os| os.to_str())
+        .map(|s| extensions.contains(&s.to_lowercase()))
+        .unwrap_or(false)
+}
+
+/// Applies `pattern` only to the lines of `content` that match `filter`, keeping line
+/// endings intact and making at most `limit` replacements. Returns the rewritten
+/// content and the number of replacements.
+fn replace_filtered_lines(
+    content: &str,
+    pattern: &Regex,
+    replacement: &str,
+    filter: &Regex,
+    limit: Option<usize>,
+) -> (String, usize) {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut result = String::with_capacity(content.len());
+    let mut matches = 0;
+
+    for line in content.split_inclusive('\n') {
+        let count = if filter.is_match(line) { pattern.find_iter(line).count() } else { 0 };
+        let count = count.min(limit - matches);
+        if count > 0 {
+            matches += count;
+            result.push_str(&pattern.replacen(line, count, replacement));
+        } else {
+            result.push_str(line);
+        }
+    }
+
+    (result, matches)
+}
+
+/// Checks that every capture group `replacement` refers to (`$1`, `$name`, `${name}`)
+/// exists in `pattern`, since the regex crate silently expands unknown groups to nothing.
+/// `$$` is a literal `$`, as is a `$` that starts no reference.
+fn check_group_refs(pattern: &Regex, replacement: &str) -> Result<()> {
+    let is_name_char = |c: char| c == '_' || c.is_ascii_alphanumeric();
+    let mut rest = replacement;
+    while let Some(dollar) = rest.find('$') {
+        rest = &rest[dollar + 1..];
+        let name = if let Some(escaped) = rest.strip_prefix('$') {
+            rest = escaped;
+            continue;
+        } else if let Some(braced) = rest.strip_prefix('{')
+            && let Some(end) = braced.find('}')
+        {
+            rest = &braced[end + 1..];
+            &braced[..end]
+        } else {
+            let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+            let name = &rest[..end];
+            rest = &rest[end..];
+            name
+        };
+        if name.is_empty() {
+            continue;
+        }
+
+        let exists = match name.parse::<usize>() {
+            Ok(index) => index < pattern.captures_len(),
+            Err(_) => pattern.capture_names().flatten().any(|group| group == name),
+        };
+        if !exists {
+            return Err(format!(
+                "Replacement '{replacement}' refers to group '{name}', which pattern '{pattern}' \
+                 does not have (write `$$` for a literal `$`, and `${{1}}` before letters)"
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Builds the replacement that surrounds each match with `before` and `after`, taken
+/// literally.
+fn wrap_replacement(before: Option<&str>, after: Option<&str>) -> String {
+    let literal = |text: Option<&str>| text.unwrap_or_default().replace('$', "$$");
+    format!("{}${{0}}{}", literal(before), literal(after))
+}
+
+/// Replaces only the `nth` (1-based) match of `pattern` in `content`, expanding capture
+/// references in `replacement`. Returns `None` if there are fewer than `nth` matches.
+fn replace_nth(content: &str, pattern: &Regex, replacement: &str, nth: usize) -> Option<String> {
+    let captures = pattern.captures_iter(content).nth(nth - 1)?;
+    let found = captures.get(0)?;
+    let mut replaced = String::with_capacity(content.len() + replacement.len());
+    replaced.push_str(&content[..found.start()]);
+    captures.expand(replacement, &mut replaced);
+    replaced.push_str(&content[found.end()..]);
+    Some(replaced)
+}
+
+/// Replaces each match of `pattern` in `content` with the output of `command`. With a
+/// line filter, only lines that match it are rewritten. At most `limit` matches are
+/// replaced, and with `crlf` the command's newlines are written as `\r\n`. Returns the
+/// new content and the number of matches replaced.
+fn replace_with_command(
+    content: &str,
+    pattern: &Regex,
+    command: &str,
+    filter: Option<&Regex>,
+    limit: Option<usize>,
+    crlf: bool,
+) -> Result<(String, usize)> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let segments: Vec<&str> = match filter {
+        Some(_) => content.split_inclusive('\n').collect(),
+        None => vec![content],
+    };
+    let mut result = String::with_capacity(content.len());
+    let mut matches = 0;
+
+    for segment in segments {
+        if !filter.is_none_or(|f| f.is_match(segment)) {
+            result.push_str(segment);
+            continue;
+        }
+        let mut last = 0;
+        for m in pattern.find_iter(segment).take(limit - matches) {
+            result.push_str(&segment[last..m.start()]);
+            let replacement = run_replace_command(command, m.as_str())?;
+            result.push_str(&if crlf { with_crlf(&replacement) } else { replacement.into() });
+            last = m.end();
+            matches += 1;
+        }
+        result.push_str(&segment[last..]);
+    }
+
+    Ok((result, matches))
+}
+
+/// Returns `true` if `content`'s first line ends in `\r\n`, taken as the file's style.
+fn uses_crlf(content: &str) -> bool {
+    content.find('\n').is_some_and(|end| content[..end].ends_with('\r'))
+}
+
+/// Converts every line ending in `text` to `style`. Returns the converted text and the
+/// number of line endings that changed, or `None` if they all had that style already.
+fn convert_line_endings(text: &str, style: LineEnding) -> Option<(String, usize)> {
+    let crlf_count = text.matches("\r\n").count();
+    let (converted, count) = match style {
+        LineEnding::Lf if crlf_count > 0 => (text.replace("\r\n", "\n"), crlf_count),
+        LineEnding::Crlf => match text.matches('\n').count() - crlf_count {
+            0 => return None,
+            bare_count => (with_crlf(text).into_owned(), bare_count),
+        },
+        LineEnding::Lf => return None,
+    };
+    Some((converted, count))
+}
+
+/// Turns each bare `\n` in `text` into `\r\n`, leaving existing `\r\n`s alone.
+fn with_crlf(text: &str) -> Cow<'_, str> {
+    if !text.contains('\n') {
+        return Cow::Borrowed(text);
+    }
+    let mut converted = String::with_capacity(text.len() + 8);
+    let mut previous = None;
+    for c in text.chars() {
+        if c == '\n' && previous != Some('\r') {
+            converted.push('\r');
+        }
+        converted.push(c);
+        previous = Some(c);
+    }
+    Cow::Owned(converted)
+}
+
+/// Runs the `--replace-cmd` command with `input` on stdin and returns its stdout. A
+/// trailing newline is dropped unless `input` ends with one too.
+fn run_replace_command(command: &str, input: &str) -> Result<String> {
+    let mut child = shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run `{command}`: {e}"))?;
+
+    // Write from another thread so a command that fills its stdout pipe before reading
+    // all of stdin cannot deadlock
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input_owned = input.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input_owned.as_bytes()));
+    let output = child.wait_with_output()?;
+    // A command that exits without reading its input closes the pipe; that is not an error
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`{command}` exited with {}: {}", output.status, stderr.trim()).into());
+    }
+    let mut replacement = String::from_utf8(output.stdout)
+        .map_err(|_| format!("`{command}` wrote output that is not valid UTF-8"))?;
+    if !input.ends_with('\n') && replacement.ends_with('\n') {
+        replacement.pop();
+        if replacement.ends_with('\r') {
+            replacement.pop();
+        }
+    }
+    Ok(replacement)
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(not(unix))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+/// Cleans up excessive empty lines from a string.
+///
+/// This is a heuristic to improve formatting after blocks of code have been removed.
+/// It collapses multiple empty lines and removes leading/trailing empty lines.
+fn clean_empty_lines(content: &str) -> String {
     // First pass: collapse multiple consecutive empty lines to at most 2
     let lines: Vec<&str> = content.lines().collect();
     let mut result = Vec::new();
@@ -518,3 +2003,856 @@ fn clean_empty_lines(content: &str) -> String {
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Args, Commands};
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn config(patterns: &[&str], replacements: &[Option<&str>]) -> ReplaceConfig {
+        ReplaceConfig {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            replacements: replacements.iter().map(|r| r.map(str::to_string)).collect(),
+            limits: vec![],
+            blocks: vec![],
+            extensions: None,
+            exclude: None,
+            ignore_patterns: vec![],
+            multiline_anchors: false,
+            line_endings: None,
+        }
+    }
+
+    fn write_options() -> ProcessOptions {
+        ProcessOptions {
+            create_backup: false,
+            backup_dir: None,
+            backup_timestamp: None,
+            dry_run: false,
+            inplace_hardlinks: false,
+            collect_edits: false,
+            collect_hunks: false,
+            print_diff: false,
+            preserve_owner: false,
+        }
+    }
+
+    fn replace_args(argv: &[&str]) -> ReplaceArgs {
+        let argv = ["ob", "replace"].iter().chain(argv);
+        match Args::try_parse_from(argv).unwrap().command {
+            Commands::Replace(args) => *args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_on_change_runs_for_each_modified_file() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "foo\n").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "foo\n").unwrap();
+        fs::write(temp_dir.path().join("c.txt"), "bar\n").unwrap();
+
+        let dir = temp_dir.path().to_str().unwrap();
+        run_replace(replace_args(&[
+            "-d", dir, "-p", "foo", "-r", "baz", "--no-backup", "--on-change", "touch {}.ran",
+        ]))
+        .unwrap();
+
+        assert!(temp_dir.path().join("a.txt.ran").exists());
+        assert!(temp_dir.path().join("b.txt.ran").exists());
+        assert!(!temp_dir.path().join("c.txt.ran").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlinks_preserved_only_when_inplace() {
+        let temp_dir = TempDir::new().unwrap();
+        let original = temp_dir.path().join("original.txt");
+        let link = temp_dir.path().join("link.txt");
+        fs::write(&original, "foo\n").unwrap();
+        fs::hard_link(&original, &link).unwrap();
+
+        let replacer = Replacer::new(config(&["foo"], &[Some("bar")])).unwrap();
+        let options = |inplace_hardlinks| ProcessOptions {
+            inplace_hardlinks,
+            ..write_options()
+        };
+
+        // Atomic write replaces the inode, so the other link keeps the old content
+        replacer.process_file(&original, options(false)).unwrap();
+        assert_eq!(fs::read_to_string(&original).unwrap(), "bar\n");
+        assert_eq!(fs::read_to_string(&link).unwrap(), "foo\n");
+
+        // In-place write goes through the shared inode, so both links change
+        let inplace = temp_dir.path().join("inplace.txt");
+        fs::hard_link(&link, &inplace).unwrap();
+        replacer.process_file(&inplace, options(true)).unwrap();
+        assert_eq!(fs::read_to_string(&inplace).unwrap(), "bar\n");
+        assert_eq!(fs::read_to_string(&link).unwrap(), "bar\n");
+    }
+
+    #[test]
+    fn test_replace_only_captured_span() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("settings.py");
+        fs::write(&path, "api_key = \"sk-123\"  # other = \"sk-123\"\n").unwrap();
+
+        let replacer =
+            Replacer::new(config(&[r#"(api_key = ")([^"]+)"#], &[Some("${1}REDACTED")])).unwrap();
+        let result = replacer.process_file(&path, write_options()).unwrap();
+
+        assert_eq!(result.changes, 1);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "api_key = \"REDACTED\"  # other = \"sk-123\"\n"
+        );
+    }
+
+    #[test]
+    fn test_dry_run_json_report_matches_real_run() {
+        let dry_dir = TempDir::new().unwrap();
+        let real_dir = TempDir::new().unwrap();
+        for dir in [&dry_dir, &real_dir] {
+            fs::create_dir(dir.path().join("src")).unwrap();
+            fs::write(dir.path().join("src/a.js"), "console.log(1);\n// TODO\nconsole.log(2);\n")
+                .unwrap();
+            fs::write(dir.path().join("b.js"), "// TODO\n").unwrap();
+            fs::write(dir.path().join("c.js"), "clean\n").unwrap();
+        }
+        let config_path = dry_dir.path().join("rules.yaml");
+        fs::write(
+            &config_path,
+            "patterns: ['console\\.log', 'TODO']\nreplacements: ['log', 'DONE']\n",
+        )
+        .unwrap();
+        let config_path = config_path.to_str().unwrap();
+
+        let run = |dir: &TempDir, extra: &[&str]| {
+            let mut argv = vec!["-d", dir.path().to_str().unwrap(), "-c", config_path];
+            argv.extend(["-x", "js", "--no-backup", "--format", "json"]);
+            argv.extend(extra);
+            replace_with_report(replace_args(&argv)).unwrap()
+        };
+        let dry = run(&dry_dir, &["--dry-run"]);
+        let real = run(&real_dir, &[]);
+
+        assert!(dry.dry_run && !real.dry_run);
+        assert_eq!(dry.files, real.files);
+        assert_eq!(dry.total_changes, 4);
+        assert_eq!(dry.files.len(), 2);
+        assert_eq!(dry.files[0].path, PathBuf::from("b.js"));
+        assert_eq!(dry.files[1].path, Path::new("src").join("a.js"));
+        assert_eq!(dry.files[1].patterns["console\\.log"], 2);
+        assert_eq!(dry.files[1].patterns["TODO"], 1);
+
+        let json: serde_json::Value = serde_json::to_value(&dry).unwrap();
+        assert_eq!(json["files"][1]["changes"], 3);
+
+        // The dry run left every file untouched
+        let a = fs::read_to_string(dry_dir.path().join("src/a.js")).unwrap();
+        assert_eq!(a, "console.log(1);\n// TODO\nconsole.log(2);\n");
+        assert_eq!(fs::read_to_string(dry_dir.path().join("b.js")).unwrap(), "// TODO\n");
+        assert_eq!(fs::read_to_string(real_dir.path().join("b.js")).unwrap(), "// DONE\n");
+    }
+
+    #[test]
+    fn test_line_filter_limits_replacement_to_matching_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.ts");
+        fs::write(
+            &path,
+            "import { old } from './old';\nconst x = old();\nimport old2 from 'old';\n",
+        )
+        .unwrap();
+
+        let replacer = Replacer::new(config(&["old"], &[Some("new")]))
+            .unwrap()
+            .with_line_filter(Some("^import"))
+            .unwrap();
+        let result = replacer.process_file(&path, write_options()).unwrap();
+
+        assert_eq!(result.changes, 4);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "import { new } from './new';\nconst x = old();\nimport new2 from 'new';\n"
+        );
+    }
+
+    #[test]
+    fn test_dry_run_sarif_contains_fixes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.js");
+        let original = "const a = 1;\nconsole.log(a);\nconst b = 2;\n";
+        fs::write(&path, original).unwrap();
+
+        let dir = temp_dir.path().to_str().unwrap();
+        let args = replace_args(&[
+            "-d", dir, "-p", r"console\.log", "-r", "logger.debug", "--dry-run", "-f", "sarif",
+        ]);
+        let report = replace_with_report(args).unwrap();
+        let sarif: serde_json::Value =
+            serde_json::from_str(&format_sarif_fixes(&report).unwrap()).unwrap();
+
+        let result = &sarif["runs"][0]["results"][0];
+        let change = &result["fixes"][0]["artifactChanges"][0];
+        assert_eq!(change["artifactLocation"]["uri"], "app.js");
+        let replacement = &change["replacements"][0];
+        assert_eq!(replacement["insertedContent"]["text"], "logger.debug(a);");
+        assert_eq!(replacement["deletedRegion"]["startLine"], 2);
+        assert_eq!(replacement["deletedRegion"]["endColumn"], 16);
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_dry_run_github_suggestions() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.js");
+        let original = "const a = 1;\nconsole.log(a);\nconsole.log(a);\nconst b = 2;\n";
+        fs::write(&path, original).unwrap();
+
+        let dir = temp_dir.path().to_str().unwrap();
+        let argv =
+            ["-d", dir, "-p", r"console\.log", "-r", "logger.debug", "-f", "github-suggestions"];
+        assert!(replace_with_report(replace_args(&argv)).is_err());
+
+        let args = replace_args(&[&argv[..], &["--dry-run"]].concat());
+        let report = replace_with_report(args).unwrap();
+        assert_eq!(
+            format_github_suggestions(&report),
+            "app.js:2-3\n```suggestion\nlogger.debug(a);\nlogger.debug(a);\n```\n"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_line_hunks_anchor_inserted_lines() {
+        let hunk = |start_line, end_line, lines: &[&str]| LineHunk {
+            start_line,
+            end_line,
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+        };
+        assert_eq!(line_hunks("a\nb\n", "a\nx\nb\n"), vec![hunk(1, 1, &["a", "x"])]);
+        assert_eq!(line_hunks("a\nb\n", "x\na\nb\n"), vec![hunk(1, 1, &["x", "a"])]);
+        assert_eq!(line_hunks("a\nb\nc\n", "a\nc\n"), vec![hunk(2, 2, &[])]);
+        assert_eq!(line_hunks("a\nb", "a\nb\nc"), vec![hunk(2, 2, &["b", "c"])]);
+    }
+
+    #[test]
+    fn test_line_edits_cover_removed_lines() {
+        let edits = line_edits("a\nb\nc\n", "a\nc\n");
+        assert_eq!(
+            edits,
+            vec![TextEdit {
+                start_line: 2,
+                start_column: 1,
+                end_line: 3,
+                end_column: 1,
+                inserted: String::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_repeat_until_fixed_point() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("arrows.txt");
+        let replacer = || Replacer::new(config(&["--"], &[Some("-")])).unwrap();
+
+        // One pass leaves a `--` behind, because the matches do not overlap
+        fs::write(&path, "a---b\n").unwrap();
+        let result = replacer().process_file(&path, write_options()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a--b\n");
+        assert_eq!(result.passes, 1);
+
+        fs::write(&path, "a---b\n").unwrap();
+        let result = replacer()
+            .with_max_passes(DEFAULT_MAX_PASSES)
+            .process_file(&path, write_options())
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a-b\n");
+        assert_eq!(result.passes, 2);
+        assert_eq!(result.changes, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preserve_owner_restores_uid_and_gid() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("owned.txt");
+        fs::write(&path, "foo\n").unwrap();
+
+        // Hand the file to another user when we have the privileges to; otherwise the
+        // restore still runs, with our own uid/gid as the recorded owner
+        let foreign = std::os::unix::fs::chown(&path, Some(12345), Some(12345)).is_ok();
+        let before = fs::metadata(&path).unwrap();
+
+        let replacer = Replacer::new(config(&["foo"], &[Some("bar")])).unwrap();
+        let options = ProcessOptions { preserve_owner: true, ..write_options() };
+        replacer.process_file(&path, options).unwrap();
+
+        let after = fs::metadata(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "bar\n");
+        assert_ne!(after.ino(), before.ino(), "expected an atomic rewrite");
+        assert_eq!((after.uid(), after.gid()), (before.uid(), before.gid()));
+        if foreign {
+            assert_eq!(after.uid(), 12345);
+        }
+    }
+
+    #[test]
+    fn test_replacement_file_inserts_multiline_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let header = "/*\n * Copyright (c) Example Corp.\n * Licensed under MIT.\n */\n";
+        let header_path = temp_dir.path().join("header.txt");
+        fs::write(&header_path, header).unwrap();
+        let src = temp_dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+        fs::write(src.join("main.rs"), "// LICENSE-HEADER\nfn main() {}\n").unwrap();
+
+        let args = replace_args(&[
+            "-d", src.to_str().unwrap(), "-p", r"// LICENSE-HEADER\n",
+            "--replacement-file", header_path.to_str().unwrap(), "--no-backup",
+            "-f", "json",
+        ]);
+        replace_with_report(args).unwrap();
+
+        // The file is inserted verbatim, trailing newline included
+        assert_eq!(
+            fs::read_to_string(src.join("main.rs")).unwrap(),
+            format!("{header}fn main() {{}}\n")
+        );
+
+        let conflict = ["ob", "replace", "-d", ".", "-p", "x", "-r", "y", "--replacement-file", "f"];
+        assert!(Args::try_parse_from(conflict).is_err());
+    }
+
+    #[test]
+    fn test_incremental_replace_skips_unchanged_files() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "nothing here\n").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "or here\n").unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        let run = |pattern: &str| {
+            let args = replace_args(&[
+                "-d", dir, "-p", pattern, "-r", "x", "--no-backup", "--incremental", "--content-hash",
+                "-f", "json",
+            ]);
+            replace_with_report(args).unwrap().files_scanned
+        };
+
+        assert_eq!(run("TODO"), 2);
+        assert_eq!(run("TODO"), 0);
+
+        // A changed file is reprocessed, and changed rules invalidate the whole cache
+        fs::write(temp_dir.path().join("b.txt"), "TODO: now\n").unwrap();
+        assert_eq!(run("TODO"), 1);
+        assert_eq!(fs::read_to_string(temp_dir.path().join("b.txt")).unwrap(), "x: now\n");
+        assert_eq!(run("FIXME"), 2);
+    }
+
+    #[test]
+    fn test_multiline_anchors_match_at_each_line_start() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.ts");
+        let original = "import a from 'a';\nconst x = 1; // import b\nimport c from 'c';\n";
+
+        fs::write(&path, original).unwrap();
+        let replacer = Replacer::new(config(&["^import"], &[Some("export")])).unwrap();
+        assert_eq!(replacer.process_file(&path, write_options()).unwrap().changes, 1);
+
+        fs::write(&path, original).unwrap();
+        let mut multiline = config(&["^import"], &[Some("export")]);
+        multiline.multiline_anchors = true;
+        let replacer = Replacer::new(multiline).unwrap();
+        assert_eq!(replacer.process_file(&path, write_options()).unwrap().changes, 2);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "export a from 'a';\nconst x = 1; // import b\nexport c from 'c';\n"
+        );
+    }
+
+    #[test]
+    fn test_process_file_errors_name_the_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("latin1.txt");
+        fs::write(&path, b"caf\xe9 TODO\n").unwrap();
+
+        let replacer = Replacer::new(config(&["TODO"], &[Some("DONE")])).unwrap();
+        let err = replacer.process_file(&path, write_options()).err().unwrap();
+        let message = err.to_string();
+        assert!(message.starts_with("File processing failed for "), "{message}");
+        assert!(message.contains("latin1.txt"), "{message}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replace_cmd_pipes_each_match_through_command() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.js");
+        fs::write(&path, "call(\"alpha\");\ncall(\"beta\");\nother(\"gamma\");\n").unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+
+        let args = replace_args(&[
+            "-d", dir, "-p", r#"call\("\w+"\)"#, "--replace-cmd", "tr a-z A-Z", "--no-backup",
+            "-f", "json",
+        ]);
+        let report = replace_with_report(args).unwrap();
+        assert_eq!(report.total_changes, 2);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "CALL(\"ALPHA\");\nCALL(\"BETA\");\nother(\"gamma\");\n"
+        );
+
+        // A failing command leaves the file untouched
+        let args = replace_args(&[
+            "-d", dir, "-p", "other", "--replace-cmd", "exit 3", "--no-backup", "-f", "json",
+        ]);
+        assert_eq!(replace_with_report(args).unwrap().files_changed, 0);
+        assert!(fs::read_to_string(&path).unwrap().ends_with("other(\"gamma\");\n"));
+        assert!(run_replace_command("exit 3", "x").unwrap_err().to_string().contains("exit"));
+    }
+
+    #[test]
+    fn test_pattern_limit_caps_changes_per_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("limits.txt");
+        let mut limited = config(&["foo", "bar"], &[Some("x"), Some("y")]);
+        limited.limits = vec![Some(1)];
+
+        fs::write(&path, "foo bar foo\nbar foo\n").unwrap();
+        let result = Replacer::new(limited.clone())
+            .unwrap()
+            .process_file(&path, write_options())
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "x y foo\ny foo\n");
+        assert_eq!(result.changes_by_pattern["foo"], 1);
+        assert_eq!(result.changes_by_pattern["bar"], 2);
+
+        // The limit holds across passes, so later passes cannot use it up again
+        fs::write(&path, "foo bar foo\nbar foo\n").unwrap();
+        let result = Replacer::new(limited)
+            .unwrap()
+            .with_max_passes(DEFAULT_MAX_PASSES)
+            .process_file(&path, write_options())
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "x y foo\ny foo\n");
+        assert_eq!(result.changes, 3);
+    }
+
+    #[test]
+    fn test_inserted_newlines_follow_crlf_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("main.c");
+        let header = "/*\n * Copyright\n */";
+        let replacer = || Replacer::new(config(&["^// HEADER"], &[Some(header)])).unwrap();
+
+        fs::write(&path, "// HEADER\r\nint x;\r\n").unwrap();
+        replacer().process_file(&path, write_options()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "/*\r\n * Copyright\r\n */\r\nint x;\r\n");
+
+        // LF files get the replacement as written
+        fs::write(&path, "// HEADER\nint x;\n").unwrap();
+        replacer().process_file(&path, write_options()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "/*\n * Copyright\n */\nint x;\n");
+    }
+
+    #[test]
+    fn test_fixed_strings_replace_literal_text() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("deps.txt");
+        fs::write(&path, "lib 1.2.3 and 1x2y3\n").unwrap();
+
+        let dir = temp_dir.path().to_str().unwrap();
+        let argv = ["-d", dir, "-F", "-p", "1.2.3", "-r", "2.0.0", "--no-backup"];
+        replace_with_report(replace_args(&argv)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "lib 2.0.0 and 1x2y3\n");
+    }
+
+    #[test]
+    fn test_require_clean_tree_refuses_uncommitted_changes() {
+        let repo = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").arg("-C").arg(repo.path()).args(args).output();
+            assert!(status.unwrap().status.success(), "git {args:?} failed");
+        };
+        let path = repo.path().join("a.txt");
+        let dir = repo.path().to_str().unwrap();
+        let argv = ["-d", dir, "-p", "foo", "-r", "bar", "--no-backup", "--require-clean-tree"];
+
+        // Not a repository yet: allowed
+        fs::write(&path, "foo\n").unwrap();
+        replace_with_report(replace_args(&argv)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "bar\n");
+
+        git(&["init", "-q"]);
+        git(&["add", "a.txt"]);
+        git(&[
+            "-c", "user.name=Ann Author", "-c", "user.email=ann@example.com",
+            "-c", "commit.gpgsign=false", "commit", "-qm", "Add a",
+        ]);
+        let argv = ["-d", dir, "-p", "bar", "-r", "baz", "--no-backup", "--require-clean-tree"];
+        replace_with_report(replace_args(&argv)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "baz\n");
+
+        // The previous run left a.txt modified, so the tree is now dirty
+        let argv = ["-d", dir, "-p", "baz", "-r", "qux", "--no-backup", "--require-clean-tree"];
+        let err = replace_with_report(replace_args(&argv)).unwrap_err();
+        assert!(err.to_string().contains("uncommitted changes"), "{err}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "baz\n");
+
+        let forced: Vec<&str> = argv.iter().copied().chain(["--force"]).collect();
+        replace_with_report(replace_args(&forced)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "qux\n");
+
+        // A repository git cannot read is not taken to be clean
+        fs::write(repo.path().join(".git/index"), "garbage\n").unwrap();
+        let argv = ["-d", dir, "-p", "qux", "-r", "quux", "--no-backup", "--require-clean-tree"];
+        let err = replace_with_report(replace_args(&argv)).unwrap_err();
+        assert!(err.to_string().contains("git status failed"), "{err}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "qux\n");
+    }
+
+    #[test]
+    fn test_nth_replaces_only_that_match_per_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("calls.txt");
+        fs::write(&path, "call(1)\ncall(2)\ncall(3) call(4)\ncall(5)\n").unwrap();
+
+        let dir = temp_dir.path().to_str().unwrap();
+        let argv =
+            ["-d", dir, "-p", r"call\((\d)\)", "-r", "invoke($1)", "--nth", "3", "--no-backup"];
+        let report = replace_with_report(replace_args(&argv)).unwrap();
+
+        assert_eq!(report.total_changes, 1);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "call(1)\ncall(2)\ninvoke(3) call(4)\ncall(5)\n"
+        );
+
+        // A file with fewer matches is left alone
+        let argv = ["-d", dir, "-p", "invoke", "-r", "x", "--nth", "2", "--dry-run"];
+        assert_eq!(replace_with_report(replace_args(&argv)).unwrap().total_changes, 0);
+    }
+
+    #[test]
+    fn test_wrap_surrounds_matches_and_keeps_other_text() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("notes.txt");
+        fs::write(&path, "the secret is secret, not public\n").unwrap();
+
+        let args = replace_args(&[
+            "-d", temp_dir.path().to_str().unwrap(), "-p", "secret", "--wrap-before", "<<$",
+            "--wrap-after", ">>", "--no-backup", "-f", "json",
+        ]);
+        replace_with_report(args).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "the <<$secret>> is <<$secret>>, not public\n"
+        );
+
+        let conflict = ["ob", "replace", "-d", ".", "-p", "x", "-r", "y", "--wrap-after", ">"];
+        assert!(Args::try_parse_from(conflict).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_replaces_in_linked_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let (dir, shared) = (temp_dir.path().join("dir"), temp_dir.path().join("shared"));
+        fs::create_dir(&dir).unwrap();
+        fs::create_dir(&shared).unwrap();
+        fs::write(shared.join("b.txt"), "foo\n").unwrap();
+        std::os::unix::fs::symlink(&shared, dir.join("linked")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("loop")).unwrap();
+
+        let replace = |extra: &[&str]| {
+            let mut argv =
+                vec!["-d", dir.to_str().unwrap(), "-p", "foo", "-r", "bar", "--no-backup"];
+            argv.extend_from_slice(extra);
+            run_replace(replace_args(&argv)).unwrap();
+            fs::read_to_string(shared.join("b.txt")).unwrap()
+        };
+
+        assert_eq!(replace(&[]), "foo\n");
+        assert_eq!(replace(&["--follow-symlinks"]), "bar\n");
+    }
+
+    #[test]
+    fn test_hidden_and_no_ignore_files_are_replaced_but_not_git() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir(dir.join(".git")).unwrap();
+        fs::write(dir.join(".git/config"), "foo\n").unwrap();
+        fs::write(dir.join(".gitignore"), "ignored.txt\n").unwrap();
+        fs::write(dir.join("ignored.txt"), "foo\n").unwrap();
+        fs::write(dir.join(".env"), "foo\n").unwrap();
+
+        let replace = |extra: &[&str]| {
+            let mut argv =
+                vec!["-d", dir.to_str().unwrap(), "-p", "foo", "-r", "bar", "--no-backup"];
+            argv.extend_from_slice(extra);
+            run_replace(replace_args(&argv)).unwrap();
+            ["ignored.txt", ".env", ".git/config"]
+                .map(|name| fs::read_to_string(dir.join(name)).unwrap())
+        };
+
+        assert_eq!(replace(&[]), ["foo\n", "foo\n", "foo\n"]);
+        assert_eq!(replace(&["--no-ignore"]), ["bar\n", "foo\n", "foo\n"]);
+        assert_eq!(replace(&["--hidden"]), ["bar\n", "bar\n", "foo\n"]);
+    }
+
+    #[test]
+    fn test_backups_of_hidden_and_ignored_files_are_undone_and_cleaned() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join(".gitignore"), "ignored.txt\n").unwrap();
+        fs::write(dir.join("ignored.txt"), "foo\n").unwrap();
+        fs::create_dir(dir.join(".config")).unwrap();
+        fs::write(dir.join(".config/app.env"), "foo\n").unwrap();
+
+        let replace = || {
+            let argv = ["-d", dir.to_str().unwrap(), "-p", "foo", "-r", "bar"];
+            run_replace(replace_args(&[&argv[..], &["--hidden", "--no-ignore"]].concat()))
+                .unwrap();
+        };
+        replace();
+        let stats = Replacer::undo(dir, None, false, false).unwrap();
+        assert_eq!((stats.found, stats.restored), (2, 2));
+        assert_eq!(fs::read_to_string(dir.join("ignored.txt")).unwrap(), "foo\n");
+        assert_eq!(fs::read_to_string(dir.join(".config/app.env")).unwrap(), "foo\n");
+
+        replace();
+        run_clean_backups(dir.to_path_buf(), None, false).unwrap();
+        assert!(!dir.join("ignored.txt.bak").exists());
+        assert!(!dir.join(".config/app.env.bak").exists());
+    }
+
+    #[test]
+    fn test_normalize_line_endings_preset_converts_both_ways() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("mixed.txt");
+        fs::write(&path, "one\r\ntwo\r\nthree\n").unwrap();
+
+        let normalize = |extra: &[&str]| {
+            let mut argv = vec![
+                "-d", temp_dir.path().to_str().unwrap(), "--preset", "normalize-line-endings",
+                "--no-backup", "-f", "json",
+            ];
+            argv.extend_from_slice(extra);
+            let report = replace_with_report(replace_args(&argv)).unwrap();
+            (fs::read_to_string(&path).unwrap(), report.total_changes)
+        };
+
+        assert_eq!(normalize(&[]), ("one\ntwo\nthree\n".to_string(), 2));
+        assert_eq!(normalize(&[]), ("one\ntwo\nthree\n".to_string(), 0));
+        assert_eq!(
+            normalize(&["--line-endings", "crlf"]),
+            ("one\r\ntwo\r\nthree\r\n".to_string(), 3)
+        );
+        assert_eq!(normalize(&["--line-endings", "crlf"]).1, 0);
+    }
+
+    #[test]
+    fn test_line_endings_apply_after_the_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("notes.txt");
+        fs::write(&path, "foo\nbar\n").unwrap();
+
+        let argv = [
+            "-d", temp_dir.path().to_str().unwrap(), "-p", "foo", "-r", "a\nb",
+            "--line-endings", "crlf", "--no-backup", "-f", "json",
+        ];
+        let report = replace_with_report(replace_args(&argv)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\r\nb\r\nbar\r\n");
+        assert_eq!(report.files[0].patterns["foo"], 1);
+        assert_eq!(report.files[0].patterns["line endings -> crlf"], 3);
+    }
+
+    #[test]
+    fn test_ooberignore_is_honored_and_exclude_wins_over_reincludes() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir(dir.join("generated")).unwrap();
+        fs::create_dir(dir.join("build")).unwrap();
+        fs::write(dir.join(".ooberignore"), "generated/\n!build/\n").unwrap();
+        for name in ["main.txt", "generated/out.txt", "build/out.txt"] {
+            fs::write(dir.join(name), "foo\n").unwrap();
+        }
+
+        let argv =
+            ["-d", dir.to_str().unwrap(), "-p", "foo", "-r", "bar", "-e", "build", "--no-backup"];
+        run_replace(replace_args(&argv)).unwrap();
+        let contents = ["main.txt", "generated/out.txt", "build/out.txt"]
+            .map(|name| fs::read_to_string(dir.join(name)).unwrap());
+        assert_eq!(contents, ["bar\n", "foo\n", "foo\n"]);
+    }
+
+    #[test]
+    fn test_replacements_expand_numbered_and_named_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("pairs.txt");
+        let dir = temp_dir.path().to_str().unwrap();
+        let replace = |pattern: &str, replacement: &str| {
+            fs::write(&path, "alpha=one beta=two\n").unwrap();
+            let argv = ["-d", dir, "-p", pattern, "-r", replacement, "--no-backup"];
+            run_replace(replace_args(&argv)).unwrap();
+            fs::read_to_string(&path).unwrap()
+        };
+
+        assert_eq!(replace(r"(\w+)=(\w+)", "$2=$1"), "one=alpha two=beta\n");
+        assert_eq!(
+            replace(r"(?P<key>\w+)=(?P<value>\w+)", "${value}_is_${key}"),
+            "one_is_alpha two_is_beta\n"
+        );
+        assert_eq!(replace(r"(\w+)=(\w+)", "$$$1"), "$alpha $beta\n");
+        assert_eq!(replace(r"=(\w+)", "=${1}$$"), "alpha=one$ beta=two$\n");
+    }
+
+    #[test]
+    fn test_config_replacements_expand_groups_alongside_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.js");
+        fs::write(&path, "// DEBUG START\nlog(x);\n// DEBUG END\nlet a = b;\n").unwrap();
+        let mut config = config(&[r"let (\w+) = (\w+);"], &[Some("const ${2} = $1;")]);
+        config.blocks = vec![crate::config::Block {
+            start: "// DEBUG START".to_string(),
+            end: "// DEBUG END".to_string(),
+        }];
+
+        Replacer::new(config).unwrap().process_file(&path, write_options()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "const b = a;\n");
+    }
+
+    #[test]
+    fn test_replacements_naming_missing_groups_are_rejected() {
+        for replacement in ["$2", "${name}", "$1REDACTED", "cost: $USD"] {
+            let err = Replacer::new(config(&[r"key=(\w+)"], &[Some(replacement)]))
+                .err()
+                .unwrap_or_else(|| panic!("{replacement} was accepted"));
+            assert!(err.to_string().contains("`$$`"), "{err}");
+        }
+        for replacement in ["$1", "${1}REDACTED", "$$USD", "$", "${unclosed", "a $ b"] {
+            assert!(Replacer::new(config(&[r"key=(\w+)"], &[Some(replacement)])).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_backup_dir_mirrors_paths_and_is_restored_by_undo() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/foo.rs"), "foo\n").unwrap();
+        fs::write(root.join("top.txt"), "foo\n").unwrap();
+        let backups = root.join("backups");
+
+        let dir = root.to_str().unwrap();
+        let argv = ["-d", dir, "-p", "foo", "-r", "bar", "--backup-dir", backups.to_str().unwrap()];
+        replace_with_report(replace_args(&argv)).unwrap();
+        assert_eq!(fs::read_to_string(root.join("src/foo.rs")).unwrap(), "bar\n");
+        assert_eq!(fs::read_to_string(backups.join("src/foo.rs")).unwrap(), "foo\n");
+        assert_eq!(fs::read_to_string(backups.join("top.txt")).unwrap(), "foo\n");
+        assert!(!root.join("src/foo.rs.bak").exists());
+
+        // The backups are not rewritten by a second run inside the same tree
+        let report = replace_with_report(replace_args(&argv)).unwrap();
+        assert_eq!(report.files_scanned, 2);
+        assert_eq!(fs::read_to_string(backups.join("top.txt")).unwrap(), "foo\n");
+
+        let stats = Replacer::undo(root, Some(&backups), false, false).unwrap();
+        assert_eq!((stats.found, stats.restored), (2, 2));
+        assert_eq!(fs::read_to_string(root.join("src/foo.rs")).unwrap(), "foo\n");
+        assert_eq!(fs::read_to_string(root.join("top.txt")).unwrap(), "foo\n");
+        assert!(!backups.exists());
+
+        // clean-backups removes the tree without restoring
+        replace_with_report(replace_args(&argv)).unwrap();
+        run_clean_backups(root.to_path_buf(), Some(backups.clone()), false).unwrap();
+        assert!(!backups.exists());
+        assert_eq!(fs::read_to_string(root.join("top.txt")).unwrap(), "bar\n");
+    }
+
+    #[test]
+    fn test_timestamped_backups_are_undone_newest_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.txt"), "foo\n").unwrap();
+
+        let dir = root.to_str().unwrap();
+        replace_with_report(replace_args(&[
+            "-d", dir, "-p", "foo", "-r", "bar", "--timestamped-backups",
+        ]))
+        .unwrap();
+        let names: Vec<String> = fs::read_dir(root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".bak"))
+            .collect();
+        let name_format =
+            Regex::new(r"^a\.txt\.\d{4}-\d\d-\d\dT\d\d-\d\d-\d\d-\d{6}\.bak$").unwrap();
+        assert!(names.len() == 1 && name_format.is_match(&names[0]), "{names:?}");
+
+        // A second run straight after keeps the first run's backup
+        replace_with_report(replace_args(&[
+            "-d", dir, "-p", "bar", "-r", "baz", "--timestamped-backups",
+        ]))
+        .unwrap();
+        Replacer::undo(root, None, false, false).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "bar\n");
+        Replacer::undo(root, None, false, false).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "foo\n");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 1);
+
+        // A plain `.bak` is ordered by its modification time
+        let plain = root.join("a.txt.bak");
+        fs::write(&plain, "v0").unwrap();
+        let may = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(0, 0, 0);
+        let may: SystemTime = may.unwrap().and_utc().into();
+        fs::File::options().write(true).open(&plain).unwrap().set_modified(may).unwrap();
+        fs::write(root.join("a.txt.2024-06-01T12-00-00-000000.bak"), "v1").unwrap();
+        fs::write(root.join("a.txt.2024-06-02T09-30-00-500000.bak"), "v2").unwrap();
+        let stats = Replacer::undo(root, None, false, false).unwrap();
+        assert_eq!((stats.found, stats.restored), (3, 1));
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "v2");
+        assert!(root.join("a.txt.2024-06-01T12-00-00-000000.bak").exists());
+
+        Replacer::undo(root, None, false, true).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "v0");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 1);
+
+        // A plain `.bak` made after the timestamped backups is the newest
+        fs::write(root.join("a.txt.2024-06-01T12-00-00-000000.bak"), "v1").unwrap();
+        fs::write(&plain, "v2").unwrap();
+        Replacer::undo(root, None, false, false).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "v2");
+    }
+
+    #[test]
+    fn test_unified_diff_of_a_change() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let new = old.replace('b', "B").replace('k', "K");
+        // Changes more than twice the context apart get their own hunks
+        assert_eq!(
+            unified_diff("f.txt", old, &new, false),
+            "--- f.txt\n+++ f.txt\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,4 +8,4 @@\n h\n i\n j\n-k\n+K\n"
+        );
+
+        // Lines are lined up around insertions and removals
+        assert_eq!(
+            unified_diff("f.txt", "a\nb\nc\n", "b\nc\nd\n", false),
+            "--- f.txt\n+++ f.txt\n@@ -1,3 +1,3 @@\n-a\n b\n c\n+d\n"
+        );
+        assert_eq!(
+            unified_diff("f.txt", "one\ntwo", "one\n2\ntwo", true),
+            "--- f.txt\n+++ f.txt\n\x1b[36m@@ -1,2 +1,3 @@\x1b[0m\n one\n\x1b[32m+2\x1b[0m\n \
+             two\n\\ No newline at end of file\n"
+        );
+        assert_eq!(unified_diff("f.txt", "same\n", "same\n", false), "");
+    }
+}
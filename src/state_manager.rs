@@ -1,16 +1,23 @@
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::time::SystemTime;
 use serde::{Serialize, Deserialize};
 use crate::errors::Result;
 
+/// The layout of the `ScanState` cache file. Bump it when cached data written by one
+/// layout cannot be read correctly by the other, and teach `migrate_scan_state` to
+/// upgrade the previous layout when that is possible.
+pub const SCAN_STATE_SCHEMA: u32 = 2;
+
 /// Represents the overall state of a scan, including metadata and file details.
 /// This struct is serialized to and from a JSON file to cache scan results.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ScanState {
-    /// The version of the tool that created the state, used for compatibility checks.
+    /// The version of the tool that last wrote the state.
     pub version: String,
+    /// The layout of the state, see `SCAN_STATE_SCHEMA`.
+    pub schema_version: u32,
     /// The timestamp of the last scan.
     pub last_scan: SystemTime,
     /// A map of file paths to their last known state.
@@ -43,8 +50,47 @@ pub struct CachedMatch {
     pub pattern_name: String,
     /// The line number where the match occurred.
     pub line_number: usize,
+    /// The 1-based character column where the match starts, see `Match::column_start`.
+    #[serde(default)]
+    pub column_start: Option<usize>,
+    /// The column just past the end of the match.
+    #[serde(default)]
+    pub column_end: Option<usize>,
+    /// The byte offset in the file where the match starts, see `Match::byte_start`.
+    #[serde(default)]
+    pub byte_start: Option<u64>,
+    /// The byte offset in the file just past the end of the match.
+    #[serde(default)]
+    pub byte_end: Option<u64>,
+    /// The text the pattern matched, see `Match::matched_text`.
+    #[serde(default)]
+    pub matched_text: String,
+    /// The pattern's capture groups in the match, see `Match::captures`.
+    #[serde(default)]
+    pub captures: Vec<Option<String>>,
     /// The content of the line that matched.
     pub line_content: String,
+    /// Named capture groups reported with the match.
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    /// The lines recorded before the match, see `Match::context_before`.
+    #[serde(default)]
+    pub context_before: Vec<String>,
+    /// The lines recorded after the match.
+    #[serde(default)]
+    pub context_after: Vec<String>,
+}
+
+/// The state of the last incremental `replace` run, used to skip files that had
+/// nothing to replace and have not changed since.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplaceState {
+    /// The version of the tool that created the state, used for compatibility checks.
+    pub version: String,
+    /// A hash of the replacement rules used in the last run, to detect changes.
+    pub patterns_hash: String,
+    /// The files that had no matches in the last run, with their state at that time.
+    pub files: HashMap<PathBuf, FileState>,
 }
 
 /// Manages the persistence of scan state for a project.
@@ -117,8 +163,9 @@ impl StateManager {
     
     /// Loads the `ScanState` from the cache file for the current project.
     ///
-    /// If the cache file does not exist, or if the version in the cache file
-    /// does not match the current tool version, it returns `Ok(None)`.
+    /// A state written by another version of the tool is reused when its schema is the
+    /// current one or can be upgraded to it (see `migrate_scan_state`). If the cache file
+    /// does not exist, or its schema cannot be upgraded, it returns `Ok(None)`.
     pub fn load_state(&self) -> Result<Option<ScanState>> {
         let state_file = self.state_file_path();
         
@@ -127,13 +174,12 @@ impl StateManager {
         }
         
         let contents = fs::read_to_string(&state_file)?;
-        let state: ScanState = serde_json::from_str(&contents)?;
-        
-        // Validate version compatibility
-        if state.version != env!("CARGO_PKG_VERSION") {
-            // Version mismatch, invalidate cache
+        let Some(state) = migrate_scan_state(serde_json::from_str(&contents)?) else {
+            // Incompatible schema, invalidate cache
             return Ok(None);
-        }
+        };
+        let mut state: ScanState = serde_json::from_value(state)?;
+        state.version = env!("CARGO_PKG_VERSION").to_string();
         
         Ok(Some(state))
     }
@@ -153,17 +199,41 @@ impl StateManager {
     ///
     /// * `state` - The `ScanState` to save.
     pub fn save_state(&self, state: &ScanState) -> Result<()> {
-        let state_file = self.state_file_path();
-        let contents = serde_json::to_string_pretty(state)?;
-        
-        // Atomic write using tempfile
+        self.write_atomic(&self.state_file_path(), &serde_json::to_string_pretty(state)?)
+    }
+
+    /// Loads the `ReplaceState` of the last incremental `replace` run, if any.
+    ///
+    /// Like `load_state`, a missing file or a version mismatch yields `Ok(None)`.
+    pub fn load_replace_state(&self) -> Result<Option<ReplaceState>> {
+        let state_file = self.replace_state_file_path();
+        if !state_file.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&state_file)?;
+        let state: ReplaceState = serde_json::from_str(&contents)?;
+        if state.version != env!("CARGO_PKG_VERSION") {
+            return Ok(None);
+        }
+
+        Ok(Some(state))
+    }
+
+    /// Saves the `ReplaceState` for the current project, separately from the scan state.
+    pub fn save_replace_state(&self, state: &ReplaceState) -> Result<()> {
+        self.write_atomic(&self.replace_state_file_path(), &serde_json::to_string(state)?)
+    }
+
+    /// Writes `contents` to `path` via a temporary file in the state directory.
+    fn write_atomic(&self, path: &Path, contents: &str) -> Result<()> {
         use tempfile::NamedTempFile;
         use std::io::Write;
-        
+
         let mut temp_file = NamedTempFile::new_in(&self.state_dir)?;
         temp_file.write_all(contents.as_bytes())?;
-        temp_file.persist(state_file)?;
-        
+        temp_file.persist(path)?;
+
         Ok(())
     }
     
@@ -171,6 +241,11 @@ impl StateManager {
     fn state_file_path(&self) -> PathBuf {
         self.state_dir.join(format!("{}.json", self.project_id))
     }
+
+    /// Constructs the full path to the `replace` state file for the current project.
+    fn replace_state_file_path(&self) -> PathBuf {
+        self.state_dir.join(format!("{}.replace.json", self.project_id))
+    }
     
     /// Deletes the cache file for the current project.
     pub fn clear_cache(&self) -> Result<()> {
@@ -182,6 +257,18 @@ impl StateManager {
     }
 }
 
+/// Upgrades a raw `ScanState` to the current `SCAN_STATE_SCHEMA`, or returns `None` if
+/// its schema cannot be upgraded (e.g. it was written by a newer release, or before the
+/// schema was recorded).
+fn migrate_scan_state(state: serde_json::Value) -> Option<serde_json::Value> {
+    let schema = state.get("schema_version").and_then(serde_json::Value::as_u64);
+    match schema {
+        Some(schema) if schema == u64::from(SCAN_STATE_SCHEMA) => Some(state),
+        // Schema 1 matches lack their byte offsets, which only a rescan can recover
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,6 +281,7 @@ mod tests {
         
         let mut state = ScanState {
             version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: SCAN_STATE_SCHEMA,
             last_scan: SystemTime::now(),
             files: HashMap::new(),
             patterns_hash: "test_hash".to_string(),
@@ -221,12 +309,13 @@ mod tests {
     }
     
     #[test]
-    fn test_version_invalidation() {
+    fn test_same_schema_from_another_version_is_migrated() {
         let temp_dir = TempDir::new().unwrap();
         let manager = StateManager::new(temp_dir.path()).unwrap();
         
         let state = ScanState {
             version: "0.0.1".to_string(), // Old version
+            schema_version: SCAN_STATE_SCHEMA,
             last_scan: SystemTime::now(),
             files: HashMap::new(),
             patterns_hash: "test".to_string(),
@@ -234,9 +323,33 @@ mod tests {
         };
         
         manager.save_state(&state).unwrap();
-        let loaded = manager.load_state().unwrap();
+        let loaded = manager.load_state().unwrap().unwrap();
         
-        // Should invalidate due to version mismatch
-        assert!(loaded.is_none());
+        // Reused, and stamped with the current version
+        assert_eq!(loaded.patterns_hash, "test");
+        assert_eq!(loaded.version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_incompatible_schema_invalidation() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = StateManager::new(temp_dir.path()).unwrap();
+        let mut state = ScanState {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: SCAN_STATE_SCHEMA + 1, // Written by a newer release
+            last_scan: SystemTime::now(),
+            files: HashMap::new(),
+            patterns_hash: "test".to_string(),
+            scan_results: HashMap::new(),
+        };
+        manager.save_state(&state).unwrap();
+        assert!(manager.load_state().unwrap().is_none());
+
+        // States from before the schema was recorded have none
+        state.schema_version = SCAN_STATE_SCHEMA;
+        let mut raw = serde_json::to_value(&state).unwrap();
+        raw.as_object_mut().unwrap().remove("schema_version");
+        manager.write_atomic(&manager.state_file_path(), &raw.to_string()).unwrap();
+        assert!(manager.load_state().unwrap().is_none());
     }
 }
\ No newline at end of file